@@ -1,5 +1,8 @@
+extern crate bucket_queue;
 extern crate vecmath;
 
+use bucket_queue::{BucketQueue, LastInFirstOutQueue, Queue};
+
 use std::cmp::Reverse;
 use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque};
 use std::iter::from_fn;
@@ -25,6 +28,8 @@ pub use self::vecmath::vec3_add as vec_add;
 pub use self::vecmath::vec3_cross as vec_cross;
 pub use self::vecmath::vec3_dot as vec_dot;
 pub use self::vecmath::vec3_neg as vec_neg;
+pub use self::vecmath::vec3_scale as vec_mul;
+pub use self::vecmath::vec3_sub as vec_sub;
 
 pub const SOUTH: Point = [0, 1];
 pub const UP: Point = NORTH;
@@ -502,6 +507,54 @@ where
     None
 }
 
+/// Dial's algorithm: Dijkstra over dense `usize` states with small integer edge
+/// costs, keeping the frontier in a bucket queue instead of a binary heap.
+/// Returns the cost and the states from a start to the first state that
+/// satisfies `success`. The queue keeps one bucket per cost up to the largest
+/// one pushed, so memory and time grow with the largest path cost.
+pub fn dijkstra_buckets<FN, IN, FS>(
+    num_states: usize,
+    starts: &[usize],
+    mut successors: FN,
+    mut success: FS,
+) -> Option<(usize, Vec<usize>)>
+where
+    FN: FnMut(usize) -> IN,
+    IN: IntoIterator<Item = (usize, usize)>,
+    FS: FnMut(usize) -> bool,
+{
+    let mut dist = vec![usize::MAX; num_states];
+    let mut came_from = vec![usize::MAX; num_states];
+    let mut frontier = BucketQueue::<Vec<usize>>::new();
+    for &start in starts {
+        dist[start] = 0;
+        frontier.push(start, 0);
+    }
+    while let Some(cost) = frontier.min_priority() {
+        let current = frontier.pop_min().unwrap();
+        if cost > dist[current] {
+            continue;
+        }
+        if success(current) {
+            let mut path = vec![current];
+            while came_from[*path.last().unwrap()] != usize::MAX {
+                path.push(came_from[*path.last().unwrap()]);
+            }
+            path.reverse();
+            return Some((cost, path));
+        }
+        for (next, edge_cost) in successors(current) {
+            let new_cost = cost + edge_cost;
+            if new_cost < dist[next] {
+                dist[next] = new_cost;
+                came_from[next] = current;
+                frontier.push(next, new_cost);
+            }
+        }
+    }
+    None
+}
+
 pub fn bfs_grid<T>(
     grid: &dyn Grid<T>,
     is_valid_move: fn(&Point, &T, &Point, &T) -> bool,
@@ -545,6 +598,15 @@ where
 
 pub trait SliceExt<T> {
     fn partialy_reflects_at(&self, idx: usize) -> bool;
+    /// Total hamming distance of the elements mirrored around `idx`, stopping
+    /// early once it exceeds `limit`.
+    fn reflection_distance_at(&self, idx: usize, limit: usize) -> usize
+    where
+        T: UnsignedExt;
+    /// Every mirror position whose paired elements differ in exactly `smudges` bits.
+    fn smudged_reflections(&self, smudges: usize) -> Vec<usize>
+    where
+        T: UnsignedExt;
 }
 
 impl<T: PartialEq> SliceExt<T> for [T] {
@@ -559,6 +621,47 @@ impl<T: PartialEq> SliceExt<T> for [T] {
 
         return true;
     }
+
+    fn reflection_distance_at(&self, idx: usize, limit: usize) -> usize
+    where
+        T: UnsignedExt,
+    {
+        let dist = (self.len() - idx).min(idx);
+
+        let mut total = 0;
+        for i in 0..dist {
+            total += self[idx - i - 1].hamming_distance(&self[idx + i]);
+            if total > limit {
+                break;
+            }
+        }
+        total
+    }
+
+    fn smudged_reflections(&self, smudges: usize) -> Vec<usize>
+    where
+        T: UnsignedExt,
+    {
+        (1..self.len())
+            .filter(|&idx| self.reflection_distance_at(idx, smudges) == smudges)
+            .collect()
+    }
+}
+
+/// Value at index `at` of the lowest-degree polynomial through `values`, taken
+/// to sit at indices `0..values.len()`. Uses Newton's forward differences, so
+/// `at` may lie before or after the samples.
+pub fn extrapolate(values: &[i64], at: i64) -> i64 {
+    let mut row = values.to_vec();
+    let mut result = 0;
+    // C(at, k), kept exact: C(at, k) * (at - k) is always divisible by k + 1.
+    let mut binomial = 1;
+    for k in 0..values.len() as i64 {
+        result += binomial * row[0];
+        binomial = binomial * (at - k) / (k + 1);
+        row = row.windows(2).map(|w| w[1] - w[0]).collect();
+    }
+    result
 }
 
 use itertools::Itertools;
@@ -595,4 +698,169 @@ impl UnsignedExt for usize {
     fn hamming_distance(&self, other: &Self) -> usize {
         (self ^ other).count_ones() as usize
     }
-}
\ No newline at end of file
+}
+
+impl UnsignedExt for u64 {
+    fn hamming_distance(&self, other: &Self) -> usize {
+        (self ^ other).count_ones() as usize
+    }
+}
+
+/// Bitsets wider than one word, compared word by word.
+impl<T: UnsignedExt> UnsignedExt for Vec<T> {
+    fn hamming_distance(&self, other: &Self) -> usize {
+        self.iter()
+            .zip(other.iter())
+            .map(|(a, b)| a.hamming_distance(b))
+            .sum()
+    }
+}
+/// Fixed-size set of small integers, one bit each.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    pub fn new(len: usize) -> BitSet {
+        BitSet {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if `i` was not in the set yet.
+    pub fn insert(&mut self, i: usize) -> bool {
+        let (word, bit) = (i / 64, 1 << (i % 64));
+        let new = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        new
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn union_with(&mut self, other: &BitSet) {
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a |= b;
+        }
+    }
+
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(n, &word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| n * 64 + bit)
+        })
+    }
+}
+
+/// Tarjan's algorithm without recursion. Components come out in reverse
+/// topological order: every edge leaving a component points to one listed
+/// before it.
+pub fn strongly_connected_components(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let n = edges.len();
+    let mut index = vec![usize::MAX; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut next_index = 0;
+
+    for root in 0..n {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        let mut calls = vec![(root, 0)];
+        index[root] = next_index;
+        low[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some((v, i)) = calls.last_mut() {
+            let v = *v;
+            if let Some(&w) = edges[v].get(*i) {
+                *i += 1;
+                if index[w] == usize::MAX {
+                    index[w] = next_index;
+                    low[w] = next_index;
+                    next_index += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    calls.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
+            calls.pop();
+            if let Some(&(u, _)) = calls.last() {
+                low[u] = low[u].min(low[v]);
+            }
+            if low[v] == index[v] {
+                let mut component = Vec::new();
+                loop {
+                    let w = stack.pop().unwrap();
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
+/// Compresses the corridors of a grid into a weighted graph between
+/// `junctions`. `moves` lists the cells reachable in one step from a cell.
+/// Each corridor is followed from its junction until it reaches the next one;
+/// cells in between may have at most one way on besides the way back.
+/// Returns, per junction, the `(junction, length)` of every corridor leaving
+/// it. Corridors that dead-end are dropped.
+pub fn junction_graph<FN, IN>(junctions: &[Point], mut moves: FN) -> Vec<Vec<(usize, usize)>>
+where
+    FN: FnMut(Point) -> IN,
+    IN: IntoIterator<Item = Point>,
+{
+    let index = junctions
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| (p, i))
+        .collect::<HashMap<_, _>>();
+    let mut edges = vec![vec![]; junctions.len()];
+    for (from, &start) in junctions.iter().enumerate() {
+        for first in moves(start) {
+            let (mut prev, mut pos, mut len) = (start, first, 1);
+            while !index.contains_key(&pos) {
+                match moves(pos).into_iter().find(|&next| next != prev) {
+                    Some(next) => (prev, pos, len) = (pos, next, len + 1),
+                    None => break,
+                }
+            }
+            if let Some(&to) = index.get(&pos) {
+                if to != from {
+                    edges[from].push((to, len));
+                }
+            }
+        }
+    }
+    edges
+}
@@ -1,4 +1,4 @@
-use itertools::Itertools;
+use aoc::extrapolate;
 
 type Data = Vec<Vec<i32>>;
 
@@ -16,30 +16,17 @@ pub fn generator(input: &str) -> Data {
 
 #[aoc(day9, part1)]
 pub fn part1(input: &Data) -> i32 {
-    input.iter().map(|a| part1_recurse(a)).sum()
-}
-
-fn part1_recurse(data: &Vec<i32>) -> i32 {
-    let next: Vec<i32> = data.iter().tuple_windows().map(|(a, b)| b - a).collect();
-    if next.iter().all(|a| *a == 0) {
-        *data.iter().last().unwrap()
-    } else {
-        data.iter().last().unwrap() + part1_recurse(&next)
-    }
+    input.iter().map(|a| extend(a, a.len() as i64)).sum()
 }
 
 #[aoc(day9, part2)]
 pub fn part2(input: &Data) -> i32 {
-    input.iter().map(|a| part2_recurse(a)).sum()
+    input.iter().map(|a| extend(a, -1)).sum()
 }
 
-fn part2_recurse(data: &Vec<i32>) -> i32 {
-    let next: Vec<i32> = data.iter().tuple_windows().map(|(a, b)| b - a).collect();
-    if next.iter().all(|a| *a == 0) {
-        data[0]
-    } else {
-        data[0] - part2_recurse(&next)
-    }
+fn extend(data: &[i32], at: i64) -> i32 {
+    let values = data.iter().map(|&v| v as i64).collect::<Vec<_>>();
+    extrapolate(&values, at) as i32
 }
 #[cfg(test)]
 mod tests {
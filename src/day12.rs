@@ -1,45 +1,233 @@
 use itertools::Itertools;
+use rand::Rng;
 use rayon::iter::*;
-use std::collections::HashMap;
 
 type Data = Vec<(String, Vec<usize>)>;
-type SolutionType = usize;
-
-pub fn solve(
-    cache: &mut HashMap<(usize, usize, usize), usize>,
-    springs: &[u8],
-    blocks: &[usize],
-    i: usize,
-    bi: usize,
-    current: usize,
-) -> usize {
-    let key = (i, bi, current);
-    if let Some(x) = cache.get(&key) {
-        return *x;
-    }
-    if i == springs.len() {
-        return if bi == blocks.len() && current == 0 {
-            1
-        } else if bi == blocks.len() - 1 && blocks[bi] == current {
-            1
+type SolutionType = u128;
+
+/// DP table over (position, block index) in a flat array: `count(i, j)` is the
+/// number of ways to place `blocks[j..]` into `springs[i..]`.
+#[derive(Debug, Clone)]
+pub struct Table {
+    springs: Vec<u8>,
+    blocks: Vec<usize>,
+    run: Vec<usize>,
+    dp: Vec<u128>,
+}
+
+impl Table {
+    pub fn new(springs: &[u8], blocks: &[usize]) -> Table {
+        let n = springs.len();
+        let m = blocks.len();
+        let mut table = Table {
+            springs: springs.to_vec(),
+            blocks: blocks.to_vec(),
+            run: vec![0; n + 1],
+            dp: vec![0; (n + 1) * (m + 1)],
+        };
+
+        // Length of the run of possibly damaged springs starting at each position.
+        for i in (0..n).rev() {
+            table.run[i] = if springs[i] == b'.' {
+                0
+            } else {
+                table.run[i + 1] + 1
+            };
+        }
+
+        table.dp[n * (m + 1) + m] = 1;
+        for i in (0..n).rev() {
+            for j in 0..=m {
+                let ways = table.operational(i, j) + table.damaged(i, j);
+                table.dp[i * (m + 1) + j] = ways;
+            }
+        }
+        table
+    }
+
+    pub fn count(&self) -> u128 {
+        self.dp[0]
+    }
+
+    /// Ways to place `blocks[j..]` into `springs[i..]`.
+    pub fn suffix(&self, i: usize, j: usize) -> u128 {
+        self.dp[i * (self.blocks.len() + 1) + j]
+    }
+
+    /// The forward counterpart of the table: `prefix[i][j]` is the number of
+    /// ways to place `blocks[..j]` into `springs[..i]` so that block `j` may
+    /// start at `i`.
+    pub fn prefix(&self) -> Vec<Vec<u128>> {
+        let (n, m) = (self.springs.len(), self.blocks.len());
+        let mut prefix = vec![vec![0; m + 1]; n + 1];
+        prefix[0][0] = 1;
+        for i in 0..n {
+            for j in 0..=m {
+                let ways = prefix[i][j];
+                if ways == 0 {
+                    continue;
+                }
+                if self.springs[i] != b'#' {
+                    prefix[i + 1][j] += ways;
+                }
+                if self.fits(i, j) {
+                    prefix[self.after_block(i, j)][j + 1] += ways;
+                }
+            }
+        }
+        prefix
+    }
+
+    /// For every spring, the number of arrangements in which it is
+    /// operational and in which it is damaged, from one forward and one
+    /// backward pass over the table.
+    pub fn spring_counts(&self) -> Vec<[u128; 2]> {
+        let (n, m) = (self.springs.len(), self.blocks.len());
+        let prefix = self.prefix();
+        let mut counts = vec![[0, 0]; n];
+        // Blocks are added where they start and taken off where they end.
+        let (mut starts, mut ends) = (vec![0; n + 1], vec![0; n + 1]);
+        for i in 0..n {
+            for j in (0..=m).filter(|&j| prefix[i][j] > 0) {
+                counts[i][0] += prefix[i][j] * self.operational(i, j);
+                let ways = prefix[i][j] * self.damaged(i, j);
+                if ways > 0 {
+                    let end = i + self.blocks[j];
+                    starts[i] += ways;
+                    ends[end] += ways;
+                    if end < n {
+                        counts[end][0] += ways;
+                    }
+                }
+            }
+        }
+        let mut damaged = 0;
+        for i in 0..n {
+            damaged = damaged + starts[i] - ends[i];
+            counts[i][1] = damaged;
+        }
+        counts
+    }
+
+    /// Ways to continue with an operational spring at `i`.
+    fn operational(&self, i: usize, j: usize) -> u128 {
+        if self.springs[i] != b'#' {
+            self.suffix(i + 1, j)
+        } else {
+            0
+        }
+    }
+
+    /// Whether block `j` can start at `i`.
+    fn fits(&self, i: usize, j: usize) -> bool {
+        if j == self.blocks.len() || self.springs[i] == b'.' {
+            return false;
+        }
+        let end = i + self.blocks[j];
+        self.run[i] >= self.blocks[j] && (end == self.springs.len() || self.springs[end] != b'#')
+    }
+
+    /// Ways to continue by starting block `j` at `i`.
+    fn damaged(&self, i: usize, j: usize) -> u128 {
+        if self.fits(i, j) {
+            self.suffix(self.after_block(i, j), j + 1)
         } else {
             0
+        }
+    }
+
+    fn after_block(&self, i: usize, j: usize) -> usize {
+        (i + self.blocks[j] + 1).min(self.springs.len())
+    }
+
+    fn push_block(&self, row: &mut Vec<u8>, i: usize, j: usize) {
+        row.resize(row.len() + self.blocks[j], b'#');
+        if i + self.blocks[j] < self.springs.len() {
+            row.push(b'.');
+        }
+    }
+
+    /// Lazily enumerates every concrete row consistent with the pattern.
+    pub fn arrangements(&self) -> Arrangements<'_> {
+        let stack = if self.count() > 0 {
+            vec![(0, 0, Vec::with_capacity(self.springs.len()))]
+        } else {
+            vec![]
         };
+        Arrangements { table: self, stack }
+    }
+
+    /// Draws one consistent row uniformly at random, if there is any.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<String> {
+        if self.count() == 0 {
+            return None;
+        }
+        let (mut i, mut j) = (0, 0);
+        let mut row = Vec::with_capacity(self.springs.len());
+        while i < self.springs.len() {
+            let total = self.suffix(i, j);
+            if rng.gen_range(0..total) < self.operational(i, j) {
+                row.push(b'.');
+                i += 1;
+            } else {
+                self.push_block(&mut row, i, j);
+                i = self.after_block(i, j);
+                j += 1;
+            }
+        }
+        Some(String::from_utf8(row).unwrap())
     }
-    let mut ans = 0;
-    for c in [b'.', b'#'] {
-        if springs[i] == c || springs[i] == b'?' {
-            if c == b'.' && current == 0 {
-                ans += solve(cache, springs, blocks, i + 1, bi, 0);
-            } else if c == b'.' && current > 0 && bi < blocks.len() && blocks[bi] == current {
-                ans += solve(cache, springs, blocks, i + 1, bi + 1, 0);
-            } else if c == b'#' {
-                ans += solve(cache, springs, blocks, i + 1, bi, current + 1);
+}
+
+pub struct Arrangements<'a> {
+    table: &'a Table,
+    stack: Vec<(usize, usize, Vec<u8>)>,
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((i, j, mut row)) = self.stack.pop() {
+            if i == self.table.springs.len() {
+                return Some(String::from_utf8(row).unwrap());
+            }
+            // Only branches with a non-zero count are followed, so every path ends in a row.
+            if self.table.damaged(i, j) > 0 {
+                let mut next = row.clone();
+                self.table.push_block(&mut next, i, j);
+                self.stack.push((self.table.after_block(i, j), j + 1, next));
+            }
+            if self.table.operational(i, j) > 0 {
+                row.push(b'.');
+                self.stack.push((i + 1, j, row));
             }
         }
+        None
     }
-    cache.insert(key, ans);
-    ans
+}
+
+/// Counts the arrangements of `blocks` that fit `springs`.
+pub fn solve(springs: &[u8], blocks: &[usize]) -> u128 {
+    Table::new(springs, blocks).count()
+}
+
+/// Repeats the row `factor` times, joining the spring copies with `?`.
+pub fn unfold(springs: &str, blocks: &[usize], factor: usize) -> (String, Vec<usize>) {
+    (
+        (0..factor).map(|_| springs).join("?"),
+        (0..factor).flat_map(|_| blocks).copied().collect(),
+    )
+}
+
+pub fn solve_unfolded(input: &Data, factor: usize) -> SolutionType {
+    input
+        .par_iter()
+        .map(|(springs, blocks)| {
+            let (springs, blocks) = unfold(springs, blocks, factor);
+            solve(springs.as_bytes(), &blocks)
+        })
+        .sum()
 }
 
 #[aoc_generator(day12)]
@@ -47,10 +235,14 @@ pub fn generator(input: &str) -> Data {
     input
         .lines()
         .map(|line| {
-            let (springs, groups) = line.split_once(" ").unwrap();
+            let (springs, groups) = line.split_once(' ').unwrap_or((line, ""));
             (
                 springs.to_string(),
-                groups.split(",").map(|x| x.parse().unwrap()).collect(),
+                groups
+                    .split(',')
+                    .filter(|x| !x.is_empty())
+                    .map(|x| x.parse().unwrap())
+                    .collect(),
             )
         })
         .collect()
@@ -58,43 +250,20 @@ pub fn generator(input: &str) -> Data {
 
 #[aoc(day12, part1)]
 pub fn part1(input: &Data) -> SolutionType {
-    input
-        .par_iter()
-        .map(|(springs, blocks)| {
-            solve(
-                &mut HashMap::new(),
-                springs.as_bytes(),
-                blocks.as_slice(),
-                0,
-                0,
-                0,
-            )
-        })
-        .sum()
+    solve_unfolded(input, 1)
 }
 
 #[aoc(day12, part2)]
 pub fn part2(input: &Data) -> SolutionType {
-    input
-        .par_iter()
-        .map(|(springs, blocks)| {
-            let new_springs = (0..5).map(|_| springs).join("?");
-            let new_blocks = (0..5).flat_map(|_| blocks).copied().collect::<Vec<_>>();
-            solve(
-                &mut HashMap::new(),
-                new_springs.as_bytes(),
-                new_blocks.as_slice(),
-                0,
-                0,
-                0,
-            )
-        })
-        .sum()
+    solve_unfolded(input, 5)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
 
     const SAMPLE: &str = "???.### 1,1,3
 .??..??...?##. 1,1,3
@@ -103,6 +272,36 @@ mod tests {
 ????.######..#####. 1,6,5
 ?###???????? 3,2,1";
 
+    fn brute_force(springs: &str, blocks: &[usize]) -> HashSet<String> {
+        let unknown = springs.bytes().filter(|&c| c == b'?').count();
+        (0..1u32 << unknown)
+            .map(|mask| {
+                let mut bit = 0;
+                springs
+                    .chars()
+                    .map(|c| match c {
+                        '?' => {
+                            bit += 1;
+                            if mask >> (bit - 1) & 1 == 1 {
+                                '#'
+                            } else {
+                                '.'
+                            }
+                        }
+                        c => c,
+                    })
+                    .collect::<String>()
+            })
+            .filter(|row| {
+                row.split('.')
+                    .filter(|s| !s.is_empty())
+                    .map(str::len)
+                    .collect::<Vec<_>>()
+                    == blocks
+            })
+            .collect()
+    }
+
     #[test]
     pub fn test1() {
         assert_eq!(part1(&generator(SAMPLE)), 21);
@@ -112,4 +311,78 @@ mod tests {
     pub fn test2() {
         assert_eq!(part2(&generator(SAMPLE)), 525152);
     }
+
+    #[test]
+    pub fn test_unfold_factor() {
+        let data = generator(SAMPLE);
+        assert_eq!(solve_unfolded(&data, 0), 6);
+        assert_eq!(solve_unfolded(&data, 1), part1(&data));
+        assert_eq!(solve_unfolded(&data, 5), part2(&data));
+        assert_eq!(solve_unfolded(&generator("???.### 1,1,3"), 20), 1);
+    }
+
+    #[test]
+    pub fn test_empty_groups() {
+        assert_eq!(solve(b"", &[]), 1);
+        assert_eq!(solve(b"...", &[]), 1);
+        assert_eq!(solve(b"???", &[]), 1);
+        assert_eq!(solve(b".#.", &[]), 0);
+        assert_eq!(part1(&generator("..?.\n.?. ")), 2);
+    }
+
+    #[test]
+    pub fn test_all_unknown() {
+        // C(n - total + 1, k): choose gap positions for k blocks.
+        assert_eq!(solve(b"???", &[1]), 3);
+        assert_eq!(solve(b"?????", &[1, 1]), 6);
+        assert_eq!(solve(b"??????????", &[2, 3]), 15);
+        assert_eq!(solve(b"??", &[3]), 0);
+        // 100 cells, 5 single blocks: C(96, 5).
+        assert_eq!(solve("?".repeat(100).as_bytes(), &[1; 5]), 61_124_064);
+    }
+
+    #[test]
+    pub fn test_arrangements_match_brute_force() {
+        let extra = generator("?????????? 2,3\n.#.?#?.## 1,1,2\n?? 3\n???? ");
+        for (springs, blocks) in generator(SAMPLE).iter().chain(extra.iter()) {
+            let table = Table::new(springs.as_bytes(), blocks);
+            let enumerated = table.arrangements().collect::<Vec<_>>();
+            assert_eq!(enumerated.len() as u128, table.count());
+            assert_eq!(
+                enumerated.into_iter().collect::<HashSet<_>>(),
+                brute_force(springs, blocks)
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_spring_counts() {
+        let extra = generator("?????????? 2,3\n.#.?#?.## 1,1,2\n?? 3\n???? \n# 1");
+        for (springs, blocks) in generator(SAMPLE).iter().chain(extra.iter()) {
+            let table = Table::new(springs.as_bytes(), blocks);
+            let prefix = table.prefix();
+            assert_eq!(prefix[springs.len()][blocks.len()], table.count());
+
+            let mut expected = vec![[0, 0]; springs.len()];
+            for row in brute_force(springs, blocks) {
+                for (count, c) in expected.iter_mut().zip(row.bytes()) {
+                    count[usize::from(c == b'#')] += 1;
+                }
+            }
+            assert_eq!(table.spring_counts(), expected, "{springs} {blocks:?}");
+        }
+    }
+
+    #[test]
+    pub fn test_sample() {
+        let mut rng = SmallRng::seed_from_u64(12);
+        let table = Table::new(b"?###????????", &[3, 2, 1]);
+        let valid = table.arrangements().collect::<HashSet<_>>();
+        let drawn = (0..200)
+            .map(|_| table.sample(&mut rng).unwrap())
+            .collect::<HashSet<_>>();
+        assert!(drawn.is_subset(&valid));
+        assert_eq!(drawn.len(), valid.len());
+        assert_eq!(Table::new(b"??", &[3]).sample(&mut rng), None);
+    }
 }
@@ -4,128 +4,182 @@ use itertools::Itertools;
 type Data = Vec<Pattern>;
 type SolutionType = usize;
 
+/// One row or column of a pattern, bit-packed into as many words as needed.
+type Line = Vec<u64>;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Reflection {
+    /// Mirror between row `n - 1` and row `n`.
+    Row(usize),
+    /// Mirror between column `n - 1` and column `n`.
+    Column(usize),
+    MainDiagonal,
+    AntiDiagonal,
+    HalfTurn,
+    QuarterTurn,
+}
+
+impl Reflection {
+    fn score(&self) -> usize {
+        match self {
+            Reflection::Row(r) => 100 * r,
+            Reflection::Column(c) => *c,
+            _ => 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
-struct Pattern {
-    rows: Vec<usize>,
-    cols: Vec<usize>
+pub struct Pattern {
+    cells: Vec<Vec<bool>>,
+    rows: Vec<Line>,
+    cols: Vec<Line>,
 }
 
-impl Pattern {
-    fn new(input: &str) -> Pattern {
-        let column_count = input.lines().next().unwrap().len();
-        let mut cols = vec![0; column_count];
-
-        let rows = input.lines()
-            .enumerate()
-            .map(|(y, line)| {
-                line.bytes()
-                    .enumerate()
-                    .map(|(x, c)| {
-                        let is_rock = (c == b'#') as usize;
-                        cols[x] += is_rock << y;
-                        is_rock << x
-                    })
-                    .sum()
-            }).collect();
-        Pattern {
-            cols,
-            rows,
+fn pack(bits: impl Iterator<Item = bool>) -> Line {
+    let mut line = Line::new();
+    for (i, bit) in bits.enumerate() {
+        if i % 64 == 0 {
+            line.push(0);
         }
+        *line.last_mut().unwrap() |= (bit as u64) << (i % 64);
     }
+    line
 }
 
-fn duplicate_positions_or_smudged(lines: &[usize]) -> Vec<usize> {
-    let mut reflections = vec![];
+impl Pattern {
+    fn new(input: &str) -> Pattern {
+        let cells = input
+            .lines()
+            .map(|line| line.bytes().map(|c| c == b'#').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let column_count = cells.first().map_or(0, |row| row.len());
 
-    for ((_, prev), (curr_idx, curr)) in lines.iter().enumerate().tuple_windows() {
-        let dist = prev.hamming_distance(curr);
+        let rows = cells.iter().map(|row| pack(row.iter().copied())).collect();
+        let cols = (0..column_count)
+            .map(|x| pack(cells.iter().map(|row| row[x])))
+            .collect();
+        Pattern { cells, rows, cols }
+    }
 
-        if dist <= 1 {
-            reflections.push(curr_idx);
-        }
+    fn width(&self) -> usize {
+        self.cols.len()
     }
 
-    reflections
-}
+    fn height(&self) -> usize {
+        self.rows.len()
+    }
 
-fn reflection_with_smudge_at(lines: &[usize], idx: usize) -> bool {
-    let dist = (lines.len() - idx).min(idx);
+    fn is_square(&self) -> bool {
+        self.width() == self.height()
+    }
 
-    let mut has_smudge = false;
-    for i in 0..dist {
-        let dist = lines[idx - i - 1].hamming_distance(&lines[idx + i]);
+    /// Where `r` maps the cell at `(x, y)`, `None` if it has no mirror image.
+    fn image(&self, r: Reflection, x: usize, y: usize) -> Option<(usize, usize)> {
+        let (w, h) = (self.width(), self.height());
+        match r {
+            Reflection::Row(row) => (2 * row)
+                .checked_sub(y + 1)
+                .filter(|&y| y < h)
+                .map(|y| (x, y)),
+            Reflection::Column(col) => (2 * col)
+                .checked_sub(x + 1)
+                .filter(|&x| x < w)
+                .map(|x| (x, y)),
+            Reflection::HalfTurn => Some((w - 1 - x, h - 1 - y)),
+            _ if !self.is_square() => None,
+            Reflection::MainDiagonal => Some((y, x)),
+            Reflection::AntiDiagonal => Some((w - 1 - y, h - 1 - x)),
+            Reflection::QuarterTurn => Some((w - 1 - y, x)),
+        }
+    }
 
-        if dist > 0 {
-            if !has_smudge && dist == 1 {
-                has_smudge = true;
-            } else {
-                return false;
+    /// Cells that have to flip so the pattern maps onto itself under `r`. Each
+    /// orbit of `r` flips its minority value, rocks on a tie.
+    pub fn flips(&self, r: Reflection) -> Vec<(usize, usize)> {
+        let mut seen = vec![vec![false; self.width()]; self.height()];
+        let mut flips = vec![];
+        for (y, x) in (0..self.height()).cartesian_product(0..self.width()) {
+            let mut orbit = vec![];
+            let mut next = Some((x, y));
+            while let Some((cx, cy)) = next.filter(|&(cx, cy)| !seen[cy][cx]) {
+                seen[cy][cx] = true;
+                orbit.push((cx, cy));
+                next = self.image(r, cx, cy);
             }
+            let (rocks, empty): (Vec<_>, Vec<_>) =
+                orbit.into_iter().partition(|&(cx, cy)| self.cells[cy][cx]);
+            flips.extend(if rocks.len() <= empty.len() {
+                rocks
+            } else {
+                empty
+            });
+        }
+        flips
+    }
+
+    /// Every reflection of the pattern that needs exactly `smudges` cells flipped.
+    pub fn reflections(&self, smudges: usize) -> Vec<Reflection> {
+        let mut found = self
+            .rows
+            .smudged_reflections(smudges)
+            .into_iter()
+            .map(Reflection::Row)
+            .chain(
+                self.cols
+                    .smudged_reflections(smudges)
+                    .into_iter()
+                    .map(Reflection::Column),
+            )
+            .collect::<Vec<_>>();
+
+        let mut modes = vec![Reflection::HalfTurn];
+        if self.is_square() {
+            modes.extend([
+                Reflection::MainDiagonal,
+                Reflection::AntiDiagonal,
+                Reflection::QuarterTurn,
+            ]);
         }
+        found.extend(
+            modes
+                .into_iter()
+                .filter(|&mode| self.flips(mode).len() == smudges),
+        );
+        found
     }
 
-    return has_smudge;
+    /// Like [`Pattern::reflections`], together with the cells to flip for each.
+    pub fn reflections_with_flips(&self, smudges: usize) -> Vec<(Reflection, Vec<(usize, usize)>)> {
+        self.reflections(smudges)
+            .into_iter()
+            .map(|r| (r, self.flips(r)))
+            .collect()
+    }
+
+    fn score(&self, smudges: usize) -> usize {
+        self.reflections(smudges)
+            .iter()
+            .find(|r| matches!(r, Reflection::Row(_) | Reflection::Column(_)))
+            .map_or(0, Reflection::score)
+    }
 }
 
 #[aoc_generator(day13)]
 fn parse(input: &str) -> Data {
-    input.split("\n\n").map(|part| {
-        Pattern::new(part)
-    }).collect()
+    input.split("\n\n").map(Pattern::new).collect()
 }
 
 #[aoc(day13, part1)]
 fn part1(input: &Data) -> SolutionType {
-    input
-        .iter()
-        .map(|p|  {
-        let row_reflections = p.rows.iter().duplicate_positions();
-        let col_reflections = p.cols.iter().duplicate_positions();
-        (p, row_reflections, col_reflections)
-    })
-        .map(|(p, row_reflections, col_reflections)| {
-        for r in row_reflections {
-            if p.rows.partialy_reflects_at(r) {
-                return 100 * r;
-            }
-        }
-        for c in col_reflections {
-            if p.cols.partialy_reflects_at(c) {
-                return c;
-            }
-        }
-        0
-    })
-        .sum()
+    input.iter().map(|p| p.score(0)).sum()
 }
 
 #[aoc(day13, part2)]
 fn part2(input: &Data) -> SolutionType {
-    input
-        .iter()
-        .map(|p|  {
-            let row_reflections = duplicate_positions_or_smudged(&p.rows);
-            let col_reflections = duplicate_positions_or_smudged(&p.cols);
-            (p, row_reflections, col_reflections)
-        })
-        .map(|(p, row_reflections, col_reflections)| {
-            for r in row_reflections {
-                if reflection_with_smudge_at(&p.rows, r) {
-                    return 100 * r;
-                }
-            }
-
-            for c in col_reflections {
-                if reflection_with_smudge_at(&p.cols, c) {
-                    return c;
-                }
-            }
-            0
-        })
-        .sum()
+    input.iter().map(|p| p.score(1)).sum()
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,4 +209,100 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(SAMPLE)), 400);
     }
-}
\ No newline at end of file
+
+    fn noise(i: usize) -> char {
+        if ((i as u64 * 2654435761) % (1 << 32)) >> 7 & 1 == 1 {
+            '#'
+        } else {
+            '.'
+        }
+    }
+
+    fn mirrored(half: Vec<String>) -> Vec<String> {
+        half.iter().chain(half.iter().rev()).cloned().collect()
+    }
+
+    #[test]
+    fn wide_and_tall_patterns() {
+        let rows = (1..=3)
+            .map(|y| {
+                let half = (0..70).map(|x| noise(x + y)).collect::<Vec<_>>();
+                half.iter().chain(half.iter().rev()).collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(part1(&parse(&rows.join("\n"))), 70);
+
+        let mut smudged = rows.clone();
+        let flipped = if smudged[1].as_bytes()[3] == b'#' {
+            "."
+        } else {
+            "#"
+        };
+        smudged[1].replace_range(3..4, flipped);
+        assert_eq!(part1(&parse(&smudged.join("\n"))), 0);
+        assert_eq!(part2(&parse(&smudged.join("\n"))), 70);
+
+        let half = (0..65)
+            .map(|y| (0..8).map(|x| noise(y * 8 + x + 1)).collect::<String>())
+            .collect();
+        let tall = Pattern::new(&mirrored(half).join("\n"));
+        assert_eq!(tall.reflections(0), vec![Reflection::Row(65)]);
+    }
+
+    #[test]
+    fn k_smudges() {
+        let p = &parse(SAMPLE)[0];
+        assert_eq!(p.reflections(0), vec![Reflection::Column(5)]);
+        assert_eq!(
+            p.reflections_with_flips(1)[0],
+            (Reflection::Row(3), vec![(0, 0)])
+        );
+        assert_eq!(
+            p.reflections_with_flips(2)[0],
+            (Reflection::Column(1), vec![(0, 0), (0, 6)])
+        );
+        for k in 0..10 {
+            for r in p.reflections(k) {
+                assert_eq!(p.flips(r).len(), k);
+            }
+        }
+        assert_eq!(
+            [0b11usize, 0b01, 0b01, 0b10].smudged_reflections(1),
+            vec![1, 2]
+        );
+        assert_eq!(
+            [0b11usize, 0b01, 0b01, 0b10].smudged_reflections(2),
+            vec![3]
+        );
+    }
+
+    #[test]
+    fn all_reflections_and_symmetry_modes() {
+        let p = Pattern::new("#..#\n.##.\n.##.\n#..#");
+        assert_eq!(
+            p.reflections(0),
+            vec![
+                Reflection::Row(2),
+                Reflection::Column(2),
+                Reflection::HalfTurn,
+                Reflection::MainDiagonal,
+                Reflection::AntiDiagonal,
+                Reflection::QuarterTurn,
+            ]
+        );
+        assert_eq!(p.reflections(1), vec![]);
+
+        let p = Pattern::new("#.\n..");
+        assert_eq!(p.reflections(0), vec![Reflection::MainDiagonal]);
+        assert_eq!(
+            p.reflections(1),
+            vec![
+                Reflection::Row(1),
+                Reflection::Column(1),
+                Reflection::HalfTurn,
+                Reflection::AntiDiagonal,
+                Reflection::QuarterTurn,
+            ]
+        );
+    }
+}
@@ -1,8 +1,9 @@
 use crate::day14::SpaceElement::*;
 use aoc::*;
-use itertools::Itertools;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt::Formatter;
+use std::rc::Rc;
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub enum SpaceElement {
@@ -10,7 +11,9 @@ pub enum SpaceElement {
     Cube,
 }
 
-type Data = HashMap<Point, SpaceElement>;
+type Rocks = HashMap<Point, SpaceElement>;
+/// The rocks and the largest `[x, y]` of the parsed text.
+type Data = (Rocks, Point);
 
 fn parse_helper(c: char) -> Option<SpaceElement> {
     match c {
@@ -34,24 +37,110 @@ impl std::fmt::Display for SpaceElement {
     }
 }
 
+pub const SPIN_CYCLE: [Point; 4] = [NORTH, WEST, SOUTH, EAST];
+
+/// Rocks on a tiltable platform, compared by their `to_key`.
+pub trait Platform: Clone {
+    fn tilt(&mut self, direction: Point);
+    /// Round rocks as one bit per cell, row by row.
+    fn to_key(&self) -> BitSet;
+    fn score(&self) -> i64;
+
+    fn spin(&mut self, sequence: &[Point]) {
+        for &c in sequence {
+            self.tilt(c);
+        }
+    }
+
+    fn cycle(&mut self) {
+        self.spin(&SPIN_CYCLE);
+    }
+
+    /// Spins with `sequence` until a state repeats.
+    fn spin_cycle(&self, sequence: &[Point]) -> Spin<Self> {
+        let mut seen = HashMap::new();
+        let mut states = vec![self.clone()];
+        loop {
+            let current = states.last().unwrap();
+            match seen.entry(current.to_key()) {
+                Entry::Occupied(entry) => {
+                    let prefix = *entry.get();
+                    let period = states.len() - 1 - prefix;
+                    states.pop();
+                    return Spin {
+                        states,
+                        prefix,
+                        period,
+                    };
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(states.len() - 1);
+                }
+            }
+            let mut next = current.clone();
+            next.spin(sequence);
+            states.push(next);
+        }
+    }
+}
+
+/// Every state of a spun platform up to the first repetition: states
+/// `prefix..prefix + period` repeat forever.
+#[derive(Debug, Clone)]
+pub struct Spin<P> {
+    states: Vec<P>,
+    prefix: usize,
+    period: usize,
+}
+
+impl<P: Platform> Spin<P> {
+    pub fn prefix(&self) -> usize {
+        self.prefix
+    }
+
+    pub fn period(&self) -> usize {
+        self.period
+    }
+
+    /// The platform after `n` spins.
+    pub fn state_after(&self, n: usize) -> &P {
+        if n < self.prefix {
+            &self.states[n]
+        } else {
+            &self.states[self.prefix + (n - self.prefix) % self.period]
+        }
+    }
+
+    pub fn load_after(&self, n: usize) -> i64 {
+        self.state_after(n).score()
+    }
+
+    /// The loads of one full period, starting after `prefix` spins.
+    pub fn period_loads(&self) -> Vec<i64> {
+        self.states[self.prefix..].iter().map(P::score).collect()
+    }
+}
+
+/// Straight-forward engine that moves every rock in a `HashMap`. Kept for the
+/// `Sparse` variants, so `cargo aoc bench` can compare it against `Dish`.
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Dish {
-    data: Data,
+pub struct SparseDish {
+    data: Rocks,
     dim_x: i64,
     dim_y: i64,
 }
 
-impl Dish {
-    fn new(data: &Data) -> Dish {
-        Dish {
-            data: data.clone(),
-            dim_x: data.iter().map(|([x, _y], _)| *x).max().unwrap(),
-            dim_y: data.iter().map(|([_x, y], _)| *y).max().unwrap(),
+impl SparseDish {
+    fn new(data: &Data) -> SparseDish {
+        SparseDish {
+            data: data.0.clone(),
+            dim_x: data.1[0],
+            dim_y: data.1[1],
         }
     }
 
     #[allow(dead_code)]
-    fn print(&self) -> &Dish {
+    fn print(&self) -> &SparseDish {
         for y in 0..=self.dim_y {
             for x in 0..=self.dim_x {
                 match self.data.get(&[x, y]) {
@@ -69,7 +158,9 @@ impl Dish {
         println!();
         self
     }
+}
 
+impl Platform for SparseDish {
     fn tilt(&mut self, direction: Point) {
         let mut next = self.clone();
 
@@ -93,7 +184,7 @@ impl Dish {
             _ => unreachable!(),
         };
 
-        let insert = |next: &mut Dish, x: i64, y: i64, offset: i64, n: usize| {
+        let insert = |next: &mut SparseDish, x: i64, y: i64, offset: i64, n: usize| {
             let p = match direction {
                 NORTH => [x, offset + n as i64],
                 SOUTH => [x, offset - n as i64],
@@ -108,7 +199,8 @@ impl Dish {
             let mut moveable = Vec::<Point>::new();
             let mut offset = match direction {
                 NORTH | WEST => 0,
-                SOUTH | EAST => self.dim_x,
+                SOUTH => self.dim_y,
+                EAST => self.dim_x,
                 _ => unreachable!(),
             };
             for &inner in inn.as_slice() {
@@ -144,70 +236,150 @@ impl Dish {
         self.data = next.data;
     }
 
-    fn to_key(&self) -> Vec<u128> {
-        let mut key = Vec::new();
-        for y in 0..=self.dim_y {
-            let mut part = 0;
-            for x in 0..=self.dim_x {
-                part = (part << 1)
-                    + match self.data.get(&[x, y]) {
-                        Some(Round) => 1,
-                        _ => 0,
-                    }
-            }
-            key.push(part);
+    fn to_key(&self) -> BitSet {
+        let width = (self.dim_x + 1) as usize;
+        let mut key = BitSet::new(width * (self.dim_y + 1) as usize);
+        for ([x, y], _) in self.data.iter().filter(|&(_, s)| *s == Round) {
+            key.insert(*y as usize * width + *x as usize);
         }
         key
     }
 
-    fn cycle_until_done(&mut self) -> Dish {
-        let mut history = Vec::<Vec<u128>>::new();
-        let mut next = self.clone();
-        history.push(next.to_key());
-        loop {
-            next.cycle();
-            let key = next.to_key();
-            if history.iter().contains(&key) {
-                history.push(key.clone());
-                let current_loop = history
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, k)| **k == key)
-                    .map(|(n, _)| n)
-                    .collect::<Vec<_>>();
-                let loop_len = current_loop[1] - current_loop[0];
-                let distance = (1000000000 - current_loop[0]) % loop_len;
-                println!(
-                    "Found loop: {:?}, period: {}, distance from current: {}",
-                    current_loop, loop_len, distance
-                );
-                for _x in 0..distance {
-                    next.cycle()
+    fn score(&self) -> i64 {
+        self.data
+            .iter()
+            .filter(|&(_p, s)| *s == Round)
+            .map(|([_x, y], _s)| (self.dim_y + 1) - y)
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    start: usize,
+    step: usize,
+    len: usize,
+}
+
+/// Dense engine: the free cells between cube rocks are split into column and
+/// row segments once, so a tilt only counts the rocks per segment and
+/// refills each segment from the end it is tilted towards.
+#[derive(Debug, Clone)]
+pub struct Dish {
+    width: usize,
+    height: usize,
+    rocks: Vec<usize>,
+    columns: Rc<Vec<Segment>>,
+    rows: Rc<Vec<Segment>>,
+    column_of: Rc<Vec<usize>>,
+    row_of: Rc<Vec<usize>>,
+    counts: Vec<usize>,
+}
+
+impl Dish {
+    fn new((data, [dim_x, dim_y]): &Data) -> Dish {
+        let width = (dim_x + 1) as usize;
+        let height = (dim_y + 1) as usize;
+        let index = |x: usize, y: usize| y * width + x;
+        let mut cubes = vec![false; width * height];
+        for ([x, y], _) in data.iter().filter(|&(_, s)| *s == Cube) {
+            cubes[index(*x as usize, *y as usize)] = true;
+        }
+
+        // Walks `outer` lines of `inner` cells, `outer_step` and `step` apart.
+        let segments = |outer: usize, inner: usize, outer_step: usize, step: usize| {
+            let mut segments = Vec::new();
+            let mut segment_of = vec![usize::MAX; width * height];
+            for o in 0..outer {
+                let mut current: Option<Segment> = None;
+                for i in 0..inner {
+                    let c = o * outer_step + i * step;
+                    if cubes[c] {
+                        segments.extend(current.take());
+                        continue;
+                    }
+                    let segment = current.get_or_insert(Segment {
+                        start: c,
+                        step,
+                        len: 0,
+                    });
+                    segment.len += 1;
+                    segment_of[c] = segments.len();
                 }
-                return next;
+                segments.extend(current);
             }
-            history.push(key);
+            (segments, segment_of)
+        };
+        let (columns, column_of) = segments(width, height, 1, width);
+        let (rows, row_of) = segments(height, width, width, 1);
+
+        let mut rocks = data
+            .iter()
+            .filter(|&(_, s)| *s == Round)
+            .map(|([x, y], _)| index(*x as usize, *y as usize))
+            .collect::<Vec<_>>();
+        rocks.sort_unstable();
+        Dish {
+            width,
+            height,
+            rocks,
+            columns: Rc::new(columns),
+            rows: Rc::new(rows),
+            column_of: Rc::new(column_of),
+            row_of: Rc::new(row_of),
+            counts: Vec::new(),
         }
     }
+}
 
-    fn cycle(&mut self) {
-        for c in [NORTH, WEST, SOUTH, EAST] {
-            self.tilt(c);
+impl Platform for Dish {
+    fn tilt(&mut self, direction: Point) {
+        let (segments, segment_of) = match direction {
+            NORTH | SOUTH => (&self.columns, &self.column_of),
+            EAST | WEST => (&self.rows, &self.row_of),
+            _ => unreachable!(),
+        };
+        self.counts.clear();
+        self.counts.resize(segments.len(), 0);
+        for &rock in &self.rocks {
+            self.counts[segment_of[rock]] += 1;
+        }
+        self.rocks.clear();
+        for (segment, &count) in segments.iter().zip(self.counts.iter()) {
+            let first = match direction {
+                NORTH | WEST => 0,
+                _ => segment.len - count,
+            };
+            self.rocks
+                .extend((first..first + count).map(|n| segment.start + n * segment.step));
         }
     }
 
+    fn to_key(&self) -> BitSet {
+        let mut key = BitSet::new(self.width * self.height);
+        for &rock in &self.rocks {
+            key.insert(rock);
+        }
+        key
+    }
+
     fn score(&self) -> i64 {
-        self.data
+        self.rocks
             .iter()
-            .filter(|&(_p, s)| *s == Round)
-            .map(|([_x, y], _s)| (self.dim_y + 1) - y)
+            .map(|rock| (self.height - rock / self.width) as i64)
             .sum()
     }
 }
 
 #[aoc_generator(day14)]
 pub fn generator(input: &str) -> Data {
-    parse_grid_to_sparse(input.lines().collect::<Vec<_>>().as_slice(), parse_helper)
+    let lines = input.lines().collect::<Vec<_>>();
+    let dim_x = lines.iter().map(|line| line.len()).max().unwrap_or(0) as i64 - 1;
+    let dim_y = lines.len() as i64 - 1;
+    (
+        parse_grid_to_sparse(lines.as_slice(), parse_helper),
+        [dim_x, dim_y],
+    )
 }
 
 #[aoc(day14, part1)]
@@ -219,8 +391,21 @@ pub fn part1(input: &Data) -> i64 {
 
 #[aoc(day14, part2)]
 pub fn part2(input: &Data) -> i64 {
-    let mut dish = Dish::new(input);
-    dish.cycle_until_done().score()
+    let dish = Dish::new(input);
+    dish.spin_cycle(&SPIN_CYCLE).load_after(1_000_000_000)
+}
+
+#[aoc(day14, part1, Sparse)]
+pub fn part1_sparse(input: &Data) -> i64 {
+    let mut dish = SparseDish::new(input);
+    dish.tilt(NORTH);
+    dish.score()
+}
+
+#[aoc(day14, part2, Sparse)]
+pub fn part2_sparse(input: &Data) -> i64 {
+    let dish = SparseDish::new(input);
+    dish.spin_cycle(&SPIN_CYCLE).load_after(1_000_000_000)
 }
 
 #[cfg(test)]
@@ -247,4 +432,133 @@ O.#..O.#.#
     pub fn test2() {
         assert_eq!(part2(&generator(SAMPLE)), 64);
     }
+
+    #[test]
+    pub fn test_engines_agree() {
+        let data = generator(SAMPLE);
+        assert_eq!(part1_sparse(&data), 136);
+        assert_eq!(part2_sparse(&data), 64);
+
+        let mut dense = Dish::new(&data);
+        let mut sparse = SparseDish::new(&data);
+        assert_eq!(dense.to_key(), sparse.to_key());
+        for _ in 0..5 {
+            for direction in [NORTH, WEST, SOUTH, EAST] {
+                dense.tilt(direction);
+                sparse.tilt(direction);
+                assert_eq!(dense.to_key(), sparse.to_key());
+                assert_eq!(dense.score(), sparse.score());
+            }
+        }
+    }
+
+    fn assert_tilts<P: Platform>(
+        new: fn(&Data) -> P,
+        start: &str,
+        expected: &[(Point, &str, i64)],
+    ) {
+        let mut dish = new(&generator(start));
+        for &(direction, grid, score) in expected {
+            dish.tilt(direction);
+            assert_eq!(dish.to_key(), new(&generator(grid)).to_key());
+            assert_eq!(dish.score(), score);
+        }
+    }
+
+    #[test]
+    pub fn test_rectangular() {
+        let wide = [
+            (NORTH, "OO#..O\n..O.#.\n......", 11),
+            (WEST, "OO#O..\nO...#.\n......", 11),
+            (SOUTH, "..#...\nO...#.\nOO.O..", 5),
+            (EAST, "..#...\n...O#.\n...OOO", 5),
+        ];
+        let tall = [
+            (NORTH, "OO.\n#..\nO..\n...\n.#.\n...", 16),
+            (WEST, "OO.\n#..\nO..\n...\n.#.\n...", 16),
+            (SOUTH, "O..\n#..\n...\n.O.\n.#.\nO..", 10),
+            (EAST, "..O\n#..\n...\n..O\n.#.\n..O", 10),
+        ];
+        for (start, expected) in [
+            ("O.#..O\n..O.#.\n.O....", wide),
+            ("O..\n#..\n.O.\n...\nO#.\n...", tall),
+        ] {
+            assert_tilts(Dish::new, start, &expected);
+            assert_tilts(SparseDish::new, start, &expected);
+        }
+    }
+
+    #[test]
+    pub fn test_empty_edges() {
+        let data = generator("O...\n.#..\n....\n....");
+        assert_eq!(part1(&data), 4);
+        assert_eq!(part1_sparse(&data), 4);
+        assert_eq!(part2(&data), 1);
+        assert_eq!(part2_sparse(&data), 1);
+        assert_eq!(part1(&generator("....\n....")), 0);
+    }
+
+    #[test]
+    pub fn test_spin_cycle() {
+        let dish = Dish::new(&generator(SAMPLE));
+        let spin = dish.spin_cycle(&SPIN_CYCLE);
+        assert_eq!((spin.prefix(), spin.period()), (3, 7));
+        assert_eq!(spin.period_loads(), vec![69, 69, 65, 64, 65, 63, 68]);
+        assert_eq!(spin.load_after(0), dish.score());
+        assert_eq!(spin.load_after(1_000_000_000), 64);
+        assert_eq!(spin.load_after(10), spin.load_after(3));
+
+        let mut direct = dish.clone();
+        for n in 1..=25 {
+            direct.cycle();
+            assert_eq!(spin.state_after(n).to_key(), direct.to_key());
+        }
+
+        let north = dish.spin_cycle(&[NORTH]);
+        assert_eq!((north.prefix(), north.period()), (1, 1));
+        assert_eq!(north.load_after(usize::MAX), 136);
+
+        let sparse = SparseDish::new(&generator(SAMPLE)).spin_cycle(&[EAST, NORTH, SOUTH]);
+        let dense = dish.spin_cycle(&[EAST, NORTH, SOUTH]);
+        assert_eq!(sparse.period_loads(), dense.period_loads());
+        assert_eq!(sparse.load_after(12345), dense.load_after(12345));
+    }
+
+    #[test]
+    pub fn test_wide() {
+        // Two dishes that only differ past column 128.
+        let row = |round: usize| {
+            (0..150)
+                .map(|x| match x {
+                    _ if x == round => 'O',
+                    _ if x % 7 == 3 => '#',
+                    _ => '.',
+                })
+                .collect::<String>()
+        };
+        let grid = |round: usize| format!("{}\n{}\n..O", row(round), row(140));
+        let (a, b) = (generator(&grid(0)), generator(&grid(135)));
+        assert_ne!(Dish::new(&a).to_key(), Dish::new(&b).to_key());
+        assert_ne!(SparseDish::new(&a).to_key(), SparseDish::new(&b).to_key());
+
+        let mut dense = Dish::new(&b);
+        let mut sparse = SparseDish::new(&b);
+        assert_eq!(dense.to_key(), sparse.to_key());
+        for direction in [EAST, SOUTH, WEST, NORTH] {
+            dense.tilt(direction);
+            sparse.tilt(direction);
+            assert_eq!(dense.to_key(), sparse.to_key());
+        }
+
+        let spin = Dish::new(&b).spin_cycle(&SPIN_CYCLE);
+        let mut direct = Dish::new(&b);
+        for n in 1..=10 {
+            direct.cycle();
+            assert_eq!(spin.state_after(n).to_key(), direct.to_key());
+        }
+        assert_eq!(
+            spin.period_loads(),
+            SparseDish::new(&b).spin_cycle(&SPIN_CYCLE).period_loads()
+        );
+    }
 }
@@ -1,81 +1,216 @@
-use crate::day15::Op::*;
-use itertools::Itertools;
-use std::collections::VecDeque;
+use crate::day15::Instruction::*;
+use indexmap::IndexMap;
+use std::fmt::Formatter;
+use std::num::ParseIntError;
+use thiserror::Error;
+
+pub type Label = String;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Op {
-    Remove,
-    ReplaceInsert,
+pub enum Instruction {
+    Remove(Label),
+    ReplaceInsert(Label, usize),
 }
 
-type Lens = (Vec<char>, usize);
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    #[error("step {0:?} has neither '=' nor a trailing '-'")]
+    MissingOperation(String),
+    #[error("step {0:?} has an empty label")]
+    EmptyLabel(String),
+    #[error("step {0:?} has an invalid focal length: {1}")]
+    InvalidFocalLength(String, ParseIntError),
+}
 
-pub fn hash(input: &str) -> usize {
+/// The HASH algorithm with an arbitrary modulus instead of 256.
+pub fn hash_mod(input: &str, modulus: usize) -> usize {
     input
         .chars()
-        .fold(0, |acc, c| ((acc + c as usize) * 17) % 256)
+        .fold(0, |acc, c| ((acc + c as usize) * 17) % modulus)
 }
 
-#[aoc(day15, part1)]
-pub fn part1(input: &str) -> usize {
-    input.split(",").map(|part| hash(part)).sum()
+pub fn hash(input: &str) -> usize {
+    hash_mod(input, 256)
 }
 
-#[aoc(day15, part2)]
-pub fn part2(input: &str) -> usize {
-    let ins: Vec<(Op, (Vec<char>, usize))> = input
-        .split(",")
-        .map(|i| {
-            let parts = i.split_once("=");
-            if let Some((l, v)) = parts {
-                return (
-                    ReplaceInsert,
-                    (l.chars().collect::<Vec<_>>(), v.parse().unwrap()),
-                );
-            }
-            let l = i.strip_suffix("-").unwrap();
-            return (Remove, (l.chars().collect(), 0));
-        })
-        .collect::<Vec<_>>();
-
-    let mut boxes: Vec<VecDeque<Lens>> = vec![VecDeque::new(); 256];
-
-    for (op, (lens, focal)) in ins {
-        let target_box = hash(lens.iter().collect::<String>().as_str());
-        match op {
-            Remove => {
-                if let Some((index, _)) = boxes[target_box]
-                    .iter()
-                    .find_position(|(id, _)| id.eq(&lens))
-                {
-                    boxes[target_box].remove(index);
-                }
+impl Instruction {
+    pub fn label(&self) -> &str {
+        match self {
+            Remove(label) | ReplaceInsert(label, _) => label,
+        }
+    }
+}
+
+impl std::str::FromStr for Instruction {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ins = if let Some((label, focal)) = s.split_once('=') {
+            let focal = focal
+                .parse()
+                .map_err(|e| ParseError::InvalidFocalLength(s.to_string(), e))?;
+            ReplaceInsert(label.to_string(), focal)
+        } else if let Some(label) = s.strip_suffix('-') {
+            Remove(label.to_string())
+        } else {
+            return Err(ParseError::MissingOperation(s.to_string()));
+        };
+        if ins.label().is_empty() {
+            return Err(ParseError::EmptyLabel(s.to_string()));
+        }
+        Ok(ins)
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Remove(label) => write!(f, "{}-", label),
+            ReplaceInsert(label, focal) => write!(f, "{}={}", label, focal),
+        }
+    }
+}
+
+/// Empty steps, as left by an empty input or a trailing comma, are skipped.
+pub fn parse(input: &str) -> Result<Vec<Instruction>, ParseError> {
+    input
+        .trim()
+        .split(',')
+        .filter(|step| !step.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Lens boxes keyed by label in insertion order, so replacing and looking up a
+/// lens never scans the box.
+#[derive(Debug, Clone)]
+pub struct Boxes {
+    boxes: Vec<IndexMap<Label, usize>>,
+    hasher: fn(&str) -> usize,
+}
+
+impl PartialEq for Boxes {
+    fn eq(&self, other: &Self) -> bool {
+        // IndexMap equality ignores order, which matters for focusing power.
+        self.boxes.len() == other.boxes.len()
+            && self
+                .boxes
+                .iter()
+                .zip(other.boxes.iter())
+                .all(|(a, b)| a.iter().eq(b.iter()))
+    }
+}
+
+impl Default for Boxes {
+    fn default() -> Self {
+        Boxes::new(256, hash)
+    }
+}
+
+impl Boxes {
+    /// `buckets` boxes, a label goes into box `hasher(label) % buckets`.
+    ///
+    /// Panics if `buckets` is zero.
+    pub fn new(buckets: usize, hasher: fn(&str) -> usize) -> Boxes {
+        assert!(buckets > 0, "there has to be at least one box");
+        Boxes {
+            boxes: vec![IndexMap::new(); buckets],
+            hasher,
+        }
+    }
+
+    fn box_of(&self, label: &str) -> usize {
+        (self.hasher)(label) % self.boxes.len()
+    }
+
+    pub fn apply(&mut self, ins: &Instruction) {
+        let n = self.box_of(ins.label());
+        let target_box = &mut self.boxes[n];
+        match ins {
+            Remove(label) => {
+                target_box.shift_remove(label);
             }
-            ReplaceInsert => {
-                if let Some((index, _)) = boxes[target_box]
-                    .iter()
-                    .find_position(|(id, _)| id.eq(&lens))
-                {
-                    boxes[target_box].get_mut(index).unwrap().1 = focal;
+            ReplaceInsert(label, focal) => {
+                if let Some(f) = target_box.get_mut(label) {
+                    *f = *focal;
                 } else {
-                    boxes[target_box].push_front((lens, focal));
+                    target_box.insert(label.clone(), *focal);
                 }
             }
         }
     }
 
-    (0..256)
-        .into_iter()
-        .map(|n| {
-            boxes[n]
-                .iter()
-                .rev()
-                .enumerate()
-                .fold(0, |acc, (id, (_lens, focal))| {
-                    acc + ((n + 1) * (id + 1) * focal)
-                })
-        })
-        .sum()
+    pub fn focal_length(&self, label: &str) -> Option<usize> {
+        self.boxes[self.box_of(label)].get(label).copied()
+    }
+
+    /// The state after each instruction, in order.
+    pub fn snapshots(&self, instructions: &[Instruction]) -> Vec<Boxes> {
+        instructions
+            .iter()
+            .scan(self.clone(), |boxes, ins| {
+                boxes.apply(ins);
+                Some(boxes.clone())
+            })
+            .collect()
+    }
+
+    pub fn lenses(&self, n: usize) -> impl Iterator<Item = (&str, usize)> {
+        self.boxes[n]
+            .iter()
+            .map(|(label, focal)| (label.as_str(), *focal))
+    }
+
+    pub fn box_focusing_power(&self, n: usize) -> usize {
+        self.lenses(n)
+            .enumerate()
+            .map(|(slot, (_, focal))| (n + 1) * (slot + 1) * focal)
+            .sum()
+    }
+
+    pub fn focusing_power(&self) -> usize {
+        (0..self.boxes.len())
+            .map(|n| self.box_focusing_power(n))
+            .sum()
+    }
+}
+
+/// Only the boxes holding lenses, one per line: `Box 0: [rn 1] [cm 2]`.
+impl std::fmt::Display for Boxes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for n in (0..self.boxes.len()).filter(|&n| !self.boxes[n].is_empty()) {
+            write!(f, "Box {}:", n)?;
+            for (label, focal) in self.lenses(n) {
+                write!(f, " [{} {}]", label, focal)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The puzzle's step-by-step walkthrough, `After "rn=1":` followed by the boxes.
+pub fn trace(instructions: &[Instruction]) -> String {
+    instructions
+        .iter()
+        .zip(Boxes::default().snapshots(instructions))
+        .map(|(ins, boxes)| format!("After \"{}\":\n{}", ins, boxes))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[aoc(day15, part1)]
+pub fn part1(input: &str) -> usize {
+    input.trim().split(',').map(hash).sum()
+}
+
+#[aoc(day15, part2)]
+pub fn part2(input: &str) -> usize {
+    let mut boxes = Boxes::default();
+    for ins in parse(input).unwrap() {
+        boxes.apply(&ins);
+    }
+    boxes.focusing_power()
 }
 
 #[cfg(test)]
@@ -87,10 +222,121 @@ mod tests {
     #[test]
     pub fn test1() {
         assert_eq!(part1(&SAMPLE), 1320);
+        assert_eq!(part1(&format!("{}\n", SAMPLE)), 1320);
     }
 
     #[test]
     pub fn test2() {
         assert_eq!(part2(&SAMPLE), 145);
+        assert_eq!(part2(&format!("{}\n", SAMPLE)), 145);
+        assert_eq!(part2(""), 0);
+        assert_eq!(part2("rn=1,"), part2("rn=1"));
+    }
+
+    #[test]
+    pub fn test_parse() {
+        assert_eq!("rn=1".parse(), Ok(ReplaceInsert("rn".to_string(), 1)));
+        assert_eq!("cm-".parse(), Ok(Remove("cm".to_string())));
+        assert_eq!(
+            "cm".parse::<Instruction>(),
+            Err(ParseError::MissingOperation("cm".to_string()))
+        );
+        assert_eq!(
+            "=4".parse::<Instruction>(),
+            Err(ParseError::EmptyLabel("=4".to_string()))
+        );
+        assert!(matches!(
+            "qp=x".parse::<Instruction>(),
+            Err(ParseError::InvalidFocalLength(..))
+        ));
+        assert_eq!(parse(SAMPLE).unwrap()[3].to_string(), "cm=2");
+    }
+
+    #[test]
+    pub fn test_trace() {
+        let instructions = parse(SAMPLE).unwrap();
+        let trace = trace(&instructions);
+        assert!(trace.starts_with(
+            "After \"rn=1\":
+Box 0: [rn 1]
+
+After \"cm-\":
+Box 0: [rn 1]
+
+After \"qp=3\":
+Box 0: [rn 1]
+Box 1: [qp 3]
+"
+        ));
+        assert!(trace.ends_with(
+            "After \"ot=7\":
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 7] [ab 5] [pc 6]
+"
+        ));
+
+        let last = Boxes::default().snapshots(&instructions).pop().unwrap();
+        assert_eq!(last.box_focusing_power(0), 5);
+        assert_eq!(last.box_focusing_power(3), 28 + 40 + 72);
+        assert_eq!(last.focusing_power(), 145);
+    }
+
+    #[test]
+    pub fn test_buckets() {
+        let instructions = parse(SAMPLE).unwrap();
+        let mut boxes = Boxes::default();
+        for ins in &instructions {
+            boxes.apply(ins);
+        }
+        assert_eq!(boxes.focal_length("pc"), Some(6));
+        assert_eq!(boxes.focal_length("qp"), None);
+
+        // With one box the order of insertion alone decides the slots.
+        let mut single = Boxes::new(1, hash);
+        for ins in &instructions {
+            single.apply(ins);
+        }
+        assert_eq!(
+            single.lenses(0).collect::<Vec<_>>(),
+            vec![("rn", 1), ("cm", 2), ("ot", 7), ("ab", 5), ("pc", 6)]
+        );
+        assert_eq!(single.focusing_power(), 1 + 4 + 21 + 20 + 30);
+
+        let mut wide = Boxes::new(4096, |label| hash_mod(label, 4093));
+        for ins in &instructions {
+            wide.apply(ins);
+        }
+        assert_eq!(wide.focal_length("ot"), Some(7));
+        assert_eq!((0..4096).map(|n| wide.lenses(n).count()).sum::<usize>(), 5);
+    }
+
+    #[test]
+    pub fn test_churn() {
+        // Churn one box and compare it with a plain ordered list.
+        let mut boxes = Boxes::new(1, hash);
+        let mut expected: Vec<(String, usize)> = vec![];
+        for i in 0..2000usize {
+            let label = format!("l{}", i * 7 % 13);
+            let ins = if i % 3 == 0 {
+                expected.retain(|(l, _)| *l != label);
+                Remove(label)
+            } else {
+                match expected.iter_mut().find(|(l, _)| *l == label) {
+                    Some((_, f)) => *f = i % 9 + 1,
+                    None => expected.push((label.clone(), i % 9 + 1)),
+                }
+                ReplaceInsert(label, i % 9 + 1)
+            };
+            boxes.apply(&ins);
+            assert!(boxes
+                .lenses(0)
+                .eq(expected.iter().map(|(l, f)| (l.as_str(), *f))));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one box")]
+    pub fn test_no_buckets() {
+        Boxes::new(0, hash);
     }
 }
@@ -1,7 +1,10 @@
 use crate::day16::Tile::*;
 use aoc::*;
+use itertools::Itertools;
 use rayon::iter::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use std::path::Path;
 
 type SolutionType = usize;
 type Data = HashMap<Point, Tile>;
@@ -15,6 +18,22 @@ pub enum Tile {
     Empty,
 }
 
+impl std::fmt::Display for Tile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Mirror(NORTH, WEST) => '/',
+                Mirror(NORTH, EAST) => '\\',
+                Splitter(WEST, EAST) => '|',
+                Splitter(NORTH, SOUTH) => '-',
+                _ => '.',
+            }
+        )
+    }
+}
+
 fn parse_helper(c: char) -> Option<Tile> {
     match c {
         '/' => Some(Mirror(NORTH, WEST)),
@@ -65,45 +84,226 @@ fn parse(input: &str) -> Data {
     parse_grid_to_sparse(input.lines().collect::<Vec<_>>().as_slice(), parse_helper)
 }
 
-fn energize(
-    energized: &mut HashSet<Point>,
-    visited: &mut HashSet<State>,
-    data: &Data,
-    current: State,
-) {
-    if let Some(t) = data.get(&current.1) {
-        energized.insert(current.1);
-        visited.insert(current);
-        let nexts = t.get_next(current.1, current.0);
-        //println!("{:?} -> {:?}, {} next: {:?}", current.0, current.1, nexts.len(), nexts);
-        for next in nexts {
-            if !visited.contains(&(current.1, next)) {
-                energize(energized, visited, data, (current.1, next));
-            } else {
-                return;
+/// Largest `[x, y]` of the grid.
+fn extents(data: &Data) -> Point {
+    [
+        data.keys().map(|[x, _y]| *x).max().unwrap_or(0),
+        data.keys().map(|[_x, y]| *y).max().unwrap_or(0),
+    ]
+}
+
+/// Bit for a beam entering `state.1` from `state.0`.
+fn state_index([dim_x, _dim_y]: Point, (from, to): State) -> usize {
+    let direction = DIRECTIONS
+        .iter()
+        .position(|&d| d == point_sub(to, from))
+        .unwrap();
+    ((to[1] * (dim_x + 1) + to[0]) * 4) as usize + direction
+}
+
+/// Inverse of [`state_index`].
+fn state_at([dim_x, _dim_y]: Point, index: usize) -> State {
+    let cell = (index / 4) as i64;
+    let to = [cell % (dim_x + 1), cell / (dim_x + 1)];
+    (point_sub(to, DIRECTIONS[index % 4]), to)
+}
+
+/// Follows every beam from `start` with an explicit work stack and returns the
+/// visited (tile, direction) states.
+fn energize(data: &Data, dims: Point, start: State) -> BitSet {
+    let mut visited = BitSet::new(((dims[0] + 1) * (dims[1] + 1) * 4) as usize);
+    let mut stack = vec![start];
+    while let Some(current) = stack.pop() {
+        if let Some(t) = data.get(&current.1) {
+            if !visited.insert(state_index(dims, current)) {
+                continue;
+            }
+            for next in t.get_next(current.1, current.0) {
+                stack.push((current.1, next));
             }
         }
     }
+    visited
 }
 
-fn generate_state() -> Vec<State> {
+/// Number of tiles with at least one visited state.
+fn energized(visited: &BitSet) -> usize {
+    visited.ones().map(|state| state / 4).dedup().count()
+}
+
+/// Every beam entering the grid from one of its edges.
+fn generate_state([dim_x, dim_y]: Point) -> Vec<State> {
     let mut initial_states = Vec::<State>::new();
-    for x in 0..110 {
+    for x in 0..=dim_x {
         initial_states.push(([x, -1], [x, 0]));
-        initial_states.push(([x, 110], [x, 109]));
+        initial_states.push(([x, dim_y + 1], [x, dim_y]));
     }
-    for y in 0..110 {
+    for y in 0..=dim_y {
         initial_states.push(([-1, y], [0, y]));
-        initial_states.push(([110, y], [109, y]));
+        initial_states.push(([dim_x + 1, y], [dim_x, y]));
     }
-    assert_eq!(initial_states.len(), 110 * 4);
     initial_states
 }
 
 fn run(input: &Data, start: State) -> usize {
-    let energized = &mut HashSet::new();
-    energize(energized, &mut HashSet::new(), &input, start);
-    energized.iter().count()
+    energized(&energize(input, extents(input), start))
+}
+
+/// Energized tile counts for every start at once. The (tile, direction)
+/// transition graph is condensed into strongly connected components, which
+/// all energize the same tiles. Each component's tiles are computed once, in
+/// reverse topological order, as its own tiles united with those of its
+/// successors; a set is dropped as soon as no predecessor needs it anymore.
+fn energize_all(data: &Data, starts: &[State]) -> Vec<usize> {
+    let dims = extents(data);
+    let cells = ((dims[0] + 1) * (dims[1] + 1)) as usize;
+    let edges = (0..cells * 4)
+        .map(|index| {
+            let (from, to) = state_at(dims, index);
+            data.get(&to).map_or(vec![], |t| {
+                t.get_next(to, from)
+                    .into_iter()
+                    .filter(|next| data.contains_key(next))
+                    .map(|next| state_index(dims, (to, next)))
+                    .collect()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let components = strongly_connected_components(&edges);
+    let mut component_of = vec![0; edges.len()];
+    for (c, states) in components.iter().enumerate() {
+        for &state in states {
+            component_of[state] = c;
+        }
+    }
+    let successors = components
+        .iter()
+        .enumerate()
+        .map(|(c, states)| {
+            states
+                .iter()
+                .flat_map(|&state| edges[state].iter().map(|&next| component_of[next]))
+                .filter(|&next| next != c)
+                .sorted()
+                .dedup()
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut pending = vec![0; components.len()];
+    for &next in successors.iter().flatten() {
+        pending[next] += 1;
+    }
+
+    let start_components = starts
+        .iter()
+        .map(|&start| component_of[state_index(dims, start)])
+        .collect::<Vec<_>>();
+    let mut counts = vec![0; components.len()];
+    let mut tiles: Vec<Option<BitSet>> = vec![None; components.len()];
+    for c in 0..components.len() {
+        let mut own = BitSet::new(cells);
+        for &state in &components[c] {
+            own.insert(state / 4);
+        }
+        for &next in &successors[c] {
+            own.union_with(tiles[next].as_ref().unwrap());
+            pending[next] -= 1;
+            if pending[next] == 0 {
+                tiles[next] = None;
+            }
+        }
+        counts[c] = own.count_ones();
+        if pending[c] > 0 {
+            tiles[c] = Some(own);
+        }
+    }
+    start_components.into_iter().map(|c| counts[c]).collect()
+}
+
+/// The states first reached after 0, 1, 2, ... steps of a breadth-first beam.
+fn beam_steps(data: &Data, dims: Point, start: State) -> Vec<Vec<usize>> {
+    let mut visited = BitSet::new(((dims[0] + 1) * (dims[1] + 1) * 4) as usize);
+    let mut steps = vec![];
+    let mut frontier = vec![start];
+    while !frontier.is_empty() {
+        let mut step = vec![];
+        let mut next_frontier = vec![];
+        for current in frontier {
+            if let Some(t) = data.get(&current.1) {
+                if visited.insert(state_index(dims, current)) {
+                    step.push(state_index(dims, current));
+                    for next in t.get_next(current.1, current.0) {
+                        next_frontier.push((current.1, next));
+                    }
+                }
+            }
+        }
+        if !step.is_empty() {
+            steps.push(step);
+        }
+        frontier = next_frontier;
+    }
+    steps
+}
+
+/// Draws the contraption like the puzzle text: mirrors and splitters as they
+/// are, empty tiles with the direction of the beam crossing them, or the
+/// number of beams if several do.
+pub fn render_beams(data: &Data, visited: &BitSet) -> String {
+    let [dim_x, dim_y] = extents(data);
+    let mut out = String::new();
+    for y in 0..=dim_y {
+        for x in 0..=dim_x {
+            let cell = ((y * (dim_x + 1) + x) * 4) as usize;
+            let beams = (0..4)
+                .filter(|d| visited.contains(cell + d))
+                .collect::<Vec<_>>();
+            match (data.get(&[x, y]), beams.as_slice()) {
+                (Some(Empty), [d]) => out.push(['^', '>', 'v', '<'][*d]),
+                (Some(Empty), []) => out.push('.'),
+                (Some(Empty), beams) => out.push_str(&beams.len().to_string()),
+                (Some(t), _) => out.push_str(&t.to_string()),
+                (None, _) => out.push(' '),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Energized tiles as `#`, everything else as `.`.
+pub fn render_energized(data: &Data, visited: &BitSet) -> String {
+    let [dim_x, dim_y] = extents(data);
+    let mut out = String::new();
+    for y in 0..=dim_y {
+        for x in 0..=dim_x {
+            let cell = ((y * (dim_x + 1) + x) * 4) as usize;
+            let lit = (0..4).any(|d| visited.contains(cell + d));
+            out.push(if lit { '#' } else { '.' });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Writes one `render_beams` frame per beam step into `dir` as
+/// `frame_0000.txt`, `frame_0001.txt`, ... and returns the number of frames.
+pub fn write_beam_frames(data: &Data, start: State, dir: &Path) -> std::io::Result<usize> {
+    let dims = extents(data);
+    std::fs::create_dir_all(dir)?;
+    let mut visited = BitSet::new(((dims[0] + 1) * (dims[1] + 1) * 4) as usize);
+    let steps = beam_steps(data, dims, start);
+    for (n, step) in steps.iter().enumerate() {
+        for &state in step {
+            visited.insert(state);
+        }
+        std::fs::write(
+            dir.join(format!("frame_{:04}.txt", n)),
+            render_beams(data, &visited),
+        )?;
+    }
+    Ok(steps.len())
 }
 
 #[aoc(day16, part1)]
@@ -113,9 +313,18 @@ fn part1(input: &Data) -> SolutionType {
 
 #[aoc(day16, part2)]
 fn part2(input: &Data) -> SolutionType {
-    generate_state()
+    let starts = generate_state(extents(input))
+        .into_iter()
+        .filter(|(_, to)| input.contains_key(to))
+        .collect::<Vec<_>>();
+    energize_all(input, &starts).into_iter().max().unwrap()
+}
+
+#[aoc(day16, part2, Bruteforce)]
+fn part2_bruteforce(input: &Data) -> SolutionType {
+    generate_state(extents(input))
         .par_iter()
-        .map(|i| run(input, i.clone()))
+        .map(|&i| run(input, i))
         .max()
         .unwrap()
 }
@@ -123,6 +332,8 @@ fn part2(input: &Data) -> SolutionType {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
 
     const SAMPLE: &str = r".|...\....
 |.-.\.....
@@ -144,4 +355,123 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(SAMPLE)), 51);
     }
+
+    #[test]
+    fn edge_states() {
+        let states = generate_state(extents(&parse(SAMPLE)));
+        assert_eq!(states.len(), 10 * 4);
+        assert!(states.contains(&([9, 10], [9, 9])));
+        assert!(states.contains(&([10, 0], [9, 0])));
+
+        let wide = parse("...\\...\n.|.....");
+        assert_eq!(generate_state(extents(&wide)).len(), 2 * 7 + 2 * 2);
+        // Best is entering the bottom row from the east and splitting at the end.
+        assert_eq!(part2(&wide), 7);
+    }
+
+    #[test]
+    fn splitter_regressions() {
+        let cases = [
+            (".|.\n...\n...", 4),
+            ("-..", 3),
+            ("\\..\n-..\n...", 4),
+            ("\\/\\\n.|/\n\\/.", 8),
+            ("..\\..\n.-.-.\n..|..\n\\.-./", 15),
+        ];
+        for (grid, expected) in cases {
+            assert_eq!(part1(&parse(grid)), expected, "{}", grid);
+        }
+    }
+
+    #[test]
+    fn long_beam_does_not_overflow() {
+        // A serpentine that walks every tile of a 1000x200 grid in one beam.
+        let (w, h) = (1000, 200);
+        let grid = (0..h)
+            .map(|y| {
+                let mut row = vec!['.'; w];
+                let edge = if y % 2 == 0 { '\\' } else { '/' };
+                row[w - 1] = edge;
+                if y > 0 {
+                    row[0] = edge;
+                }
+                row.into_iter().collect::<String>()
+            })
+            .join("\n");
+        assert_eq!(part1(&parse(&grid)), w * h);
+    }
+
+    #[test]
+    fn condensed_matches_bruteforce() {
+        let mut grids = vec![SAMPLE.to_string()];
+        let mut rng = SmallRng::seed_from_u64(17);
+        for _ in 0..20 {
+            let grid = (0..12)
+                .map(|_| {
+                    (0..9)
+                        .map(|_| b"....../\\|-"[rng.gen_range(0..10)] as char)
+                        .collect::<String>()
+                })
+                .join("\n");
+            grids.push(grid);
+        }
+        for grid in grids {
+            let data = parse(&grid);
+            let starts = generate_state(extents(&data));
+            let all = energize_all(&data, &starts);
+            for (start, count) in starts.into_iter().zip(all) {
+                assert_eq!(count, run(&data, start), "{:?}\n{}", start, grid);
+            }
+            assert_eq!(part2(&data), part2_bruteforce(&data));
+        }
+    }
+
+    #[test]
+    fn render() {
+        let data = parse(SAMPLE);
+        let visited = energize(&data, extents(&data), ([-1, 0], [0, 0]));
+        assert_eq!(
+            render_beams(&data, &visited),
+            r">|<<<\....
+|v-.\^....
+.v...|->>>
+.v...v^.|.
+.v...v^...
+.v...v^..\
+.v../2\\..
+<->-/vv|..
+.|<<<2-|.\
+.v//.|.v..
+"
+        );
+        assert_eq!(
+            render_energized(&data, &visited),
+            "######....
+.#...#....
+.#...#####
+.#...##...
+.#...##...
+.#...##...
+.#..####..
+########..
+.#######..
+.#...#.#..
+"
+        );
+    }
+
+    #[test]
+    fn frames() {
+        let data = parse(SAMPLE);
+        let dir = std::env::temp_dir().join(format!("day16_frames_{}", std::process::id()));
+        let frames = write_beam_frames(&data, ([-1, 0], [0, 0]), &dir).unwrap();
+        let first = std::fs::read_to_string(dir.join("frame_0000.txt")).unwrap();
+        let last =
+            std::fs::read_to_string(dir.join(format!("frame_{:04}.txt", frames - 1))).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(first.starts_with(">|...\\...."));
+        let visited = energize(&data, extents(&data), ([-1, 0], [0, 0]));
+        assert_eq!(last, render_beams(&data, &visited));
+    }
 }
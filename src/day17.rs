@@ -1,27 +1,267 @@
-use std::collections::{HashMap};
 use aoc::*;
+use itertools::Itertools;
 use pathfinding::prelude::dijkstra;
+use std::collections::HashMap;
 
 type Data = HashMap<Point, u32>;
 
 #[aoc_generator(day17)]
 pub fn generator(input: &str) -> Data {
-    parse_grid_to_sparse(input.lines().collect::<Vec<_>>().as_slice(), |c| c.to_digit(10))
+    parse_grid_to_sparse(input.lines().collect::<Vec<_>>().as_slice(), |c| {
+        c.to_digit(10)
+    })
 }
 
-fn solve(inputs: &Data, min_moves: i32, max_moves: i32) -> u32 {
-    let dest = [*inputs.iter().map(|([x, _y], _)| x).max().unwrap(), *inputs.iter().map(|([_x, y], _)| y).max().unwrap()];
-    dijkstra(
+/// Heat-loss map as a dense row-major grid with its width and height. Cells
+/// missing from `inputs` are `None`.
+fn cost_grid(inputs: &Data) -> (i64, i64, Vec<Option<usize>>) {
+    let (_, [max_x, max_y]) = inputs.extents();
+    let (width, height) = (max_x + 1, max_y + 1);
+    let mut costs = vec![None; (width * height) as usize];
+    for ([x, y], &cost) in inputs {
+        costs[(y * width + x) as usize] = Some(cost as usize);
+    }
+    (width, height, costs)
+}
+
+/// Fast path of [`Crucible::solve`] for the puzzle's crucible: minimal heat
+/// loss from the top-left to the bottom-right corner, with the cells entered
+/// along the way and the direction each was entered in. `None` if no path
+/// satisfies the move limits. Cells missing from `inputs` cannot be entered.
+///
+/// A state is a cell plus the axis of the straight run that ended there, densely
+/// indexed as `(y * width + x) * 2 + axis`. Every edge is a full run of
+/// `min_moves..=max_moves` cells followed by a turn onto the other axis, so
+/// run lengths never have to be part of the state. Edge weights are at most
+/// `9 * max_moves`, which keeps the bucket queue small.
+fn solve(
+    inputs: &Data,
+    min_moves: usize,
+    max_moves: usize,
+) -> Option<(usize, Vec<(Point, Point)>)> {
+    // The crucible has to move at least once.
+    if !inputs.contains_key(&[0, 0]) || inputs.len() == 1 {
+        return None;
+    }
+    let (width, height, costs) = cost_grid(inputs);
+    // No straight run can be longer than the grid.
+    let max_moves = max_moves.min(width.max(height) as usize);
+    let index = |[x, y]: Point, axis: usize| ((y * width + x) as usize) * 2 + axis;
+    let point = |state: usize| {
+        let cell = (state / 2) as i64;
+        [cell % width, cell / width]
+    };
+    let dest = [width - 1, height - 1];
+
+    let (cost, states) = dijkstra_buckets(
+        costs.len() * 2,
+        &[index([0, 0], 0), index([0, 0], 1)],
+        |state| {
+            let pos = point(state);
+            // Runs along the other axis than the one that got us here.
+            let dirs = if state % 2 == 0 {
+                [NORTH, SOUTH]
+            } else {
+                [EAST, WEST]
+            };
+            let mut next = Vec::with_capacity(2 * max_moves);
+            for dir in dirs {
+                let mut cost = 0;
+                for l in 1..=max_moves {
+                    let [x, y] = point_add(pos, point_mul(dir, l as i64));
+                    if x < 0 || y < 0 || x >= width || y >= height {
+                        break;
+                    }
+                    let Some(step) = costs[(y * width + x) as usize] else {
+                        break;
+                    };
+                    cost += step;
+                    if l >= min_moves {
+                        next.push((index([x, y], 1 - state % 2), cost));
+                    }
+                }
+            }
+            next
+        },
+        |state| point(state) == dest,
+    )?;
+
+    let mut path = Vec::new();
+    for (from, to) in states.into_iter().map(point).tuple_windows() {
+        let dir = point_signum(point_sub(to, from));
+        let mut pos = from;
+        while pos != to {
+            pos = point_add(pos, dir);
+            path.push((pos, dir));
+        }
+    }
+    Some((cost, path))
+}
+
+/// A configurable crucible: where it starts, where it may stop, and how it is
+/// allowed to move between the two.
+#[derive(Debug, Clone)]
+pub struct Crucible {
+    /// Shortest straight run before the crucible may turn or stop.
+    pub min_moves: usize,
+    /// Longest straight run before it has to turn.
+    pub max_moves: usize,
+    pub start: Point,
+    /// Cells the crucible may stop at; the cheapest one wins.
+    pub goals: Vec<Point>,
+    /// Whether the crucible may reverse onto the direction it came from.
+    pub u_turns: bool,
+    /// Extra heat loss paid for every change of direction.
+    pub turn_cost: u32,
+    /// Whether the four diagonal directions are available as well.
+    pub diagonal: bool,
+}
+
+impl Crucible {
+    /// The puzzle's crucible: from the top-left to the bottom-right corner of
+    /// `inputs`, no U-turns, free turns and orthogonal moves only.
+    pub fn new(inputs: &Data, min_moves: usize, max_moves: usize) -> Crucible {
+        let (_, max) = inputs.extents();
+        Crucible {
+            min_moves,
+            max_moves,
+            start: [0, 0],
+            goals: vec![max],
+            u_turns: false,
+            turn_cost: 0,
+            diagonal: false,
+        }
+    }
+
+    fn directions(&self) -> &'static [Point] {
+        if self.diagonal {
+            &DIRECTIONS_INCL_DIAGONALS
+        } else {
+            &DIRECTIONS
+        }
+    }
+
+    /// Minimal heat loss to any goal, with the cells entered and the direction
+    /// each was entered in. `None` if no goal is reachable. The puzzle's
+    /// crucible from [`Crucible::new`] takes the bucket-queue fast path in
+    /// [`solve`], everything else a general search.
+    pub fn solve(&self, inputs: &Data) -> Option<(usize, Vec<(Point, Point)>)> {
+        let (_, max) = inputs.extents();
+        if self.start == [0, 0]
+            && self.goals == [max]
+            && !self.u_turns
+            && self.turn_cost == 0
+            && !self.diagonal
+        {
+            solve(inputs, self.min_moves, self.max_moves)
+        } else {
+            self.search(inputs)
+        }
+    }
+
+    /// The general search behind [`Crucible::solve`]. A state is a cell, a
+    /// direction and the length of the current run, indexed as
+    /// `((y * width + x) * dirs + dir) * (max_moves + 1) + run`. Run `0` only
+    /// occurs at the start, where every direction is open. Turn costs can make
+    /// edge weights arbitrarily large, so this runs on a binary heap.
+    fn search(&self, inputs: &Data) -> Option<(usize, Vec<(Point, Point)>)> {
+        if inputs.is_empty() || !inputs.contains_key(&self.start) {
+            return None;
+        }
+        let (width, height, costs) = cost_grid(inputs);
+        let mut goals = BitSet::new(costs.len());
+        for &[x, y] in &self.goals {
+            if (0..width).contains(&x) && (0..height).contains(&y) {
+                goals.insert((y * width + x) as usize);
+            }
+        }
+
+        let dirs = self.directions();
+        // No straight run can be longer than the grid.
+        let max_moves = self.max_moves.min(width.max(height) as usize);
+        let runs = max_moves + 1;
+        let index = |[x, y]: Point, dir: usize, run: usize| {
+            ((y * width + x) as usize * dirs.len() + dir) * runs + run
+        };
+        let decode = |state: usize| {
+            let (cell, run) = (state / runs / dirs.len(), state % runs);
+            let dir = state / runs % dirs.len();
+            ([cell as i64 % width, cell as i64 / width], dir, run)
+        };
+        let cell_cost = |[x, y]: Point| {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                None
+            } else {
+                costs[(y * width + x) as usize]
+            }
+        };
+
+        let (states, cost) = dijkstra(
+            &index(self.start, 0, 0),
+            |&state| {
+                let (pos, dir, run) = decode(state);
+                let mut next = Vec::with_capacity(dirs.len());
+                for (d, &step) in dirs.iter().enumerate() {
+                    let straight = run > 0 && d == dir;
+                    let allowed = if run == 0 {
+                        true
+                    } else if straight {
+                        run < max_moves
+                    } else {
+                        run >= self.min_moves && (self.u_turns || step != point_mul(dirs[dir], -1))
+                    };
+                    let target = point_add(pos, step);
+                    if let (true, Some(cost)) = (allowed, cell_cost(target)) {
+                        let (run, turn) = if straight {
+                            (run + 1, 0)
+                        } else {
+                            (1, if run > 0 { self.turn_cost as usize } else { 0 })
+                        };
+                        next.push((index(target, d, run), cost + turn));
+                    }
+                }
+                next
+            },
+            |&state| {
+                let ([x, y], _, run) = decode(state);
+                run > 0 && run >= self.min_moves && goals.contains((y * width + x) as usize)
+            },
+        )?;
+
+        let path = states
+            .into_iter()
+            .skip(1)
+            .map(|state| {
+                let (pos, dir, _) = decode(state);
+                (pos, dirs[dir])
+            })
+            .collect();
+        Some((cost, path))
+    }
+}
+
+/// The original solver over hashed `(position, direction, run)` states. Kept for
+/// the `Hashed` variants, so `cargo aoc bench` can compare it against [`solve`].
+fn solve_hashed(
+    inputs: &Data,
+    min_moves: i32,
+    max_moves: i32,
+) -> Option<(u32, Vec<(Point, Point)>)> {
+    let dest = [
+        *inputs.iter().map(|([x, _y], _)| x).max()?,
+        *inputs.iter().map(|([_x, y], _)| y).max()?,
+    ];
+    let (path, cost) = dijkstra(
         &([0, 0], [0, 0], 0),
         |&(pos, dir, l)| {
             let mut next = Vec::with_capacity(3);
             let mut add_next = |dir, l| {
                 let next_point = point_add(pos, dir);
-                if inputs.contains_key(&next_point) {
-                    next.push(((next_point, dir, l), *inputs.get(&next_point).unwrap()));
+                if let Some(&cost) = inputs.get(&next_point) {
+                    next.push(((next_point, dir, l), cost));
                 }
             };
-            if l < max_moves {
+            if l > 0 && l < max_moves {
                 add_next(dir, l + 1);
             }
             if l >= min_moves {
@@ -33,24 +273,75 @@ fn solve(inputs: &Data, min_moves: i32, max_moves: i32) -> u32 {
             }
             next
         },
-        |&(pos, _, l) | pos == dest && l >= min_moves
-    ).unwrap().1
+        |&(pos, _, l)| pos == dest && l >= min_moves,
+    )?;
+    Some((
+        cost,
+        path.into_iter()
+            .skip(1)
+            .map(|(pos, dir, _)| (pos, dir))
+            .collect(),
+    ))
+}
+
+/// The heat-loss map with the path drawn over it like the puzzle text;
+/// diagonal steps are drawn as `\\` and `/`.
+pub fn render(inputs: &Data, path: &[(Point, Point)]) -> String {
+    let arrows = path
+        .iter()
+        .map(|&(pos, dir)| {
+            let arrow = match dir {
+                EAST => '>',
+                SOUTH => 'v',
+                WEST => '<',
+                NORTH => '^',
+                [1, 1] | [-1, -1] => '\\',
+                [1, -1] | [-1, 1] => '/',
+                _ => unreachable!(),
+            };
+            (pos, arrow)
+        })
+        .collect::<HashMap<_, _>>();
+    let ([min_x, min_y], [max_x, max_y]) = inputs.extents();
+    let mut out = String::new();
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            match (arrows.get(&[x, y]), inputs.get(&[x, y])) {
+                (Some(&arrow), _) => out.push(arrow),
+                (None, Some(cost)) => out.push_str(&cost.to_string()),
+                (None, None) => out.push(' '),
+            }
+        }
+        out.push('\n');
+    }
+    out
 }
 
 #[aoc(day17, part1)]
-pub fn part1(inputs: &Data) -> u32 {
-    solve(inputs, 1, 3)
+pub fn part1(inputs: &Data) -> usize {
+    Crucible::new(inputs, 1, 3).solve(inputs).unwrap().0
 }
 
 #[aoc(day17, part2)]
-pub fn part2(inputs: &Data) -> u32 {
-    solve(inputs, 4, 10)
+pub fn part2(inputs: &Data) -> usize {
+    Crucible::new(inputs, 4, 10).solve(inputs).unwrap().0
 }
 
+#[aoc(day17, part1, Hashed)]
+pub fn part1_hashed(inputs: &Data) -> usize {
+    solve_hashed(inputs, 1, 3).unwrap().0 as usize
+}
+
+#[aoc(day17, part2, Hashed)]
+pub fn part2_hashed(inputs: &Data) -> usize {
+    solve_hashed(inputs, 4, 10).unwrap().0 as usize
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
 
     const SAMPLE: &str = "2413432311323
 3215453535623
@@ -82,4 +373,178 @@ mod tests {
         assert_eq!(part2(&generator(SAMPLE)), 94);
         assert_eq!(part2(&generator(SAMPLE2)), 71);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn no_path() {
+        assert_eq!(solve(&generator("11"), 4, 10), None);
+        assert_eq!(solve(&generator(""), 1, 3), None);
+        assert_eq!(solve_hashed(&generator("11"), 4, 10), None);
+    }
+
+    #[test]
+    fn matches_hashed() {
+        for sample in [SAMPLE, SAMPLE2] {
+            let data = generator(sample);
+            assert_eq!(part1(&data), part1_hashed(&data));
+            assert_eq!(part2(&data), part2_hashed(&data));
+        }
+        // A single cell needs no move, which the crucible has to make.
+        assert_eq!(solve(&generator("5"), 1, 3), None);
+        assert_eq!(solve_hashed(&generator("5"), 1, 3), None);
+
+        let mut rng = SmallRng::seed_from_u64(3);
+        for round in 0..20 {
+            // Every other grid has ragged rows, whose missing cells are walls.
+            let grid = (0..9)
+                .map(|_| {
+                    let len = if round % 2 == 0 {
+                        14
+                    } else {
+                        rng.gen_range(8..=14)
+                    };
+                    (0..len)
+                        .map(|_| char::from_digit(rng.gen_range(1..=9), 10).unwrap())
+                        .collect::<String>()
+                })
+                .join("\n");
+            let data = generator(&grid);
+            for (min, max) in [(1, 3), (4, 10), (2, 5)] {
+                let cost = solve(&data, min, max).map(|s| s.0);
+                assert_eq!(
+                    cost,
+                    solve_hashed(&data, min as i32, max as i32).map(|s| s.0 as usize)
+                );
+                assert_eq!(
+                    cost,
+                    Crucible::new(&data, min, max).search(&data).map(|s| s.0)
+                );
+            }
+        }
+        let ragged = generator("1111111\n9\n1111111");
+        assert_eq!(solve(&ragged, 1, 3), None);
+        assert_eq!(solve_hashed(&ragged, 1, 3), None);
+        let ragged = generator("11111\n9999\n99991\n1111");
+        assert_eq!(solve(&ragged, 1, 3), None);
+        assert_eq!(solve_hashed(&ragged, 1, 3), None);
+    }
+
+    #[test]
+    fn render_path() {
+        let data = generator(SAMPLE);
+        let (cost, path) = solve(&data, 1, 3).unwrap();
+        assert_eq!(
+            path.iter()
+                .map(|(pos, _)| data[pos] as usize)
+                .sum::<usize>(),
+            cost
+        );
+        assert_eq!(path.last().unwrap().0, [12, 12]);
+
+        let data = generator(SAMPLE2);
+        let (_, path) = solve(&data, 4, 10).unwrap();
+        assert_eq!(
+            render(&data, &path),
+            "1>>>>>>>1111
+9999999v9991
+9999999v9991
+9999999v9991
+9999999v>>>>
+"
+        );
+    }
+
+    #[test]
+    fn search_matches_solve() {
+        for sample in [SAMPLE, SAMPLE2] {
+            let data = generator(sample);
+            for (min, max) in [(1, 3), (4, 10)] {
+                let (cost, path) = Crucible::new(&data, min, max).search(&data).unwrap();
+                assert_eq!(Some(cost), solve(&data, min, max).map(|s| s.0));
+                assert_eq!(
+                    path.iter()
+                        .map(|(pos, _)| data[pos] as usize)
+                        .sum::<usize>(),
+                    cost
+                );
+            }
+        }
+        let data = generator("11");
+        assert_eq!(Crucible::new(&data, 4, 10).solve(&data), None);
+        assert_eq!(Crucible::new(&data, 4, 10).search(&data), None);
+    }
+
+    #[test]
+    fn crucible_start_and_goals() {
+        let data = generator("1111\n9999\n1111");
+        let mut crucible = Crucible::new(&data, 1, 3);
+        crucible.goals = vec![[3, 0], [3, 2]];
+        assert_eq!(crucible.solve(&data).unwrap().0, 3);
+        crucible.start = [3, 2];
+        crucible.goals = vec![[0, 0], [0, 2]];
+        assert_eq!(crucible.solve(&data).unwrap().0, 3);
+        crucible.goals = vec![[0, 0], [9, 9]];
+        assert_eq!(crucible.solve(&data).unwrap().0, 9 + 1 + 1 + 1 + 1);
+        crucible.start = [9, 9];
+        assert_eq!(crucible.solve(&data), None);
+    }
+
+    #[test]
+    fn crucible_u_turns() {
+        // Has to overshoot by at least one cell and come back.
+        let data = generator("11111");
+        let mut crucible = Crucible::new(&data, 2, 10);
+        crucible.goals = vec![[1, 0]];
+        assert_eq!(crucible.solve(&data), None);
+        crucible.u_turns = true;
+        let (cost, path) = crucible.solve(&data).unwrap();
+        assert_eq!(cost, 5);
+        assert_eq!(
+            path,
+            vec![
+                ([1, 0], EAST),
+                ([2, 0], EAST),
+                ([3, 0], EAST),
+                ([2, 0], WEST),
+                ([1, 0], WEST)
+            ]
+        );
+    }
+
+    #[test]
+    fn crucible_turn_cost() {
+        let data = generator("11111\n11111\n11111");
+        let mut crucible = Crucible::new(&data, 1, 3);
+        assert_eq!(crucible.solve(&data).unwrap().0, 6);
+        crucible.turn_cost = 10;
+        assert_eq!(crucible.solve(&data).unwrap().0, 26);
+        crucible.max_moves = 4;
+        assert_eq!(crucible.solve(&data).unwrap().0, 16);
+
+        let data = generator("111\n111\n111");
+        let mut crucible = Crucible::new(&data, 1, 3);
+        crucible.turn_cost = u32::MAX;
+        assert_eq!(crucible.solve(&data).unwrap().0, u32::MAX as usize + 4);
+    }
+
+    #[test]
+    fn crucible_long_runs() {
+        let data = generator(SAMPLE2);
+        let crucible = Crucible::new(&data, 4, usize::MAX);
+        assert_eq!(crucible.solve(&data).unwrap().0, 11 + 4);
+        assert_eq!(crucible.search(&data).unwrap().0, 11 + 4);
+        let crucible = Crucible::new(&data, usize::MAX, usize::MAX);
+        assert_eq!(crucible.solve(&data), None);
+        assert_eq!(crucible.search(&data), None);
+    }
+
+    #[test]
+    fn crucible_diagonal() {
+        let data = generator("111\n111\n111");
+        let mut crucible = Crucible::new(&data, 1, 3);
+        assert_eq!(crucible.solve(&data).unwrap().0, 4);
+        crucible.diagonal = true;
+        let (cost, path) = crucible.solve(&data).unwrap();
+        assert_eq!(cost, 2);
+        assert_eq!(render(&data, &path), "111\n1\\1\n11\\\n");
+    }
+}
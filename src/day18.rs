@@ -0,0 +1,238 @@
+use aoc::*;
+use itertools::Itertools;
+use std::num::ParseIntError;
+use thiserror::Error;
+
+/// One straight stretch of trench.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub dir: Point,
+    pub meters: i64,
+}
+
+/// A dig plan line read both ways: as the plain `R 6` step and as the step
+/// hidden in its `(#70c710)` colour code.
+type Data = Vec<(Step, Step)>;
+type SolutionType = i64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    #[error("line {0:?} is not of the form `R 6 (#70c710)`")]
+    Malformed(String),
+    #[error("line {0:?} has an unknown direction")]
+    InvalidDirection(String),
+    #[error("line {0:?} has an invalid distance: {1}")]
+    InvalidMeters(String, ParseIntError),
+    #[error("line {0:?} has an invalid colour code")]
+    InvalidColor(String),
+    #[error("the plan ends at {0:?} instead of returning to the origin")]
+    Unclosed(Point),
+}
+
+impl std::str::FromStr for Step {
+    type Err = ParseError;
+
+    /// The plain form, `R 6`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (dir, meters) = s
+            .split_once(' ')
+            .ok_or_else(|| ParseError::Malformed(s.to_string()))?;
+        let dir = match dir {
+            "R" => EAST,
+            "D" => SOUTH,
+            "L" => WEST,
+            "U" => NORTH,
+            _ => return Err(ParseError::InvalidDirection(s.to_string())),
+        };
+        let meters = meters
+            .parse()
+            .map_err(|e| ParseError::InvalidMeters(s.to_string(), e))?;
+        Ok(Step { dir, meters })
+    }
+}
+
+impl Step {
+    /// The hex form, `#70c710`: five hex digits of distance followed by the
+    /// direction as `0..=3` for `R D L U`.
+    pub fn from_hex(s: &str) -> Result<Step, ParseError> {
+        let err = || ParseError::InvalidColor(s.to_string());
+        let hex = s
+            .strip_prefix('#')
+            .filter(|h| h.len() == 6)
+            .ok_or_else(err)?;
+        let meters = i64::from_str_radix(&hex[..5], 16).map_err(|_| err())?;
+        let dir = match &hex[5..] {
+            "0" => EAST,
+            "1" => SOUTH,
+            "2" => WEST,
+            "3" => NORTH,
+            _ => return Err(err()),
+        };
+        Ok(Step { dir, meters })
+    }
+}
+
+pub fn parse(input: &str) -> Result<Data, ParseError> {
+    input
+        .lines()
+        .map(|line| {
+            let (plain, color) = line
+                .rsplit_once(' ')
+                .and_then(|(plain, color)| {
+                    Some((plain, color.strip_prefix('(')?.strip_suffix(')')?))
+                })
+                .ok_or_else(|| ParseError::Malformed(line.to_string()))?;
+            Ok((plain.parse()?, Step::from_hex(color)?))
+        })
+        .collect()
+}
+
+#[aoc_generator(day18)]
+pub fn generator(input: &str) -> Data {
+    parse(input).unwrap()
+}
+
+/// Corners of the trench loop, starting and ending at the origin. A plan that
+/// does not lead back to the origin encloses nothing and is rejected.
+pub fn vertices<'a>(steps: impl IntoIterator<Item = &'a Step>) -> Result<Vec<Point>, ParseError> {
+    let mut pos = [0, 0];
+    let mut vertices = vec![pos];
+    for step in steps {
+        pos = point_add(pos, point_mul(step.dir, step.meters));
+        vertices.push(pos);
+    }
+    if pos != [0, 0] {
+        return Err(ParseError::Unclosed(pos));
+    }
+    Ok(vertices)
+}
+
+/// Cubic meters of lava the dug-out lagoon holds. The shoelace formula gives
+/// the area enclosed by the cell centres; Pick's theorem adds the outer half
+/// of the one meter wide trench: `A + b / 2 + 1`.
+pub fn lagoon_volume<'a>(steps: impl IntoIterator<Item = &'a Step>) -> Result<i64, ParseError> {
+    let vertices = vertices(steps)?;
+    let (twice_area, boundary) =
+        vertices
+            .iter()
+            .tuple_windows()
+            .fold((0, 0), |(area, boundary), (&[x1, y1], &[x2, y2])| {
+                (
+                    area + x1 * y2 - x2 * y1,
+                    boundary + (x2 - x1).abs() + (y2 - y1).abs(),
+                )
+            });
+    Ok(twice_area.abs() / 2 + boundary / 2 + 1)
+}
+
+#[aoc(day18, part1)]
+pub fn part1(input: &Data) -> SolutionType {
+    lagoon_volume(input.iter().map(|(plain, _)| plain)).unwrap()
+}
+
+#[aoc(day18, part2)]
+pub fn part2(input: &Data) -> SolutionType {
+    lagoon_volume(input.iter().map(|(_, hex)| hex)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "R 6 (#70c710)
+D 5 (#0dc571)
+L 2 (#5713f0)
+D 2 (#d2c081)
+R 2 (#59c680)
+D 2 (#411b91)
+L 5 (#8ceee2)
+U 2 (#caa173)
+L 1 (#1b58a2)
+U 2 (#caa171)
+R 2 (#7807d2)
+U 3 (#a77fa3)
+L 2 (#015232)
+U 2 (#7a21e3)";
+
+    #[test]
+    fn part1_example() {
+        assert_eq!(part1(&generator(SAMPLE)), 62);
+    }
+
+    #[test]
+    fn part2_example() {
+        assert_eq!(part2(&generator(SAMPLE)), 952408144115);
+    }
+
+    #[test]
+    fn parse_both_forms() {
+        let data = generator(SAMPLE);
+        assert_eq!(
+            data[0],
+            (
+                Step {
+                    dir: EAST,
+                    meters: 6
+                },
+                Step {
+                    dir: EAST,
+                    meters: 461937
+                }
+            )
+        );
+        assert_eq!(
+            data[1].1,
+            Step {
+                dir: SOUTH,
+                meters: 56407
+            }
+        );
+        assert_eq!(
+            vertices(data.iter().map(|(p, _)| p)).unwrap().last(),
+            Some(&[0, 0])
+        );
+
+        assert!(matches!(parse("R 6"), Err(ParseError::Malformed(_))));
+        assert!(matches!(
+            parse("X 6 (#70c710)"),
+            Err(ParseError::InvalidDirection(_))
+        ));
+        assert!(matches!(
+            parse("R x (#70c710)"),
+            Err(ParseError::InvalidMeters(..))
+        ));
+        assert!(matches!(
+            parse("R 6 (#70c714)"),
+            Err(ParseError::InvalidColor(_))
+        ));
+        assert!(matches!(
+            parse("R 6 (70c710)"),
+            Err(ParseError::InvalidColor(_))
+        ));
+    }
+
+    #[test]
+    fn square_and_counter_clockwise() {
+        let square = generator("R 2 (#000020)\nD 2 (#000021)\nL 2 (#000022)\nU 2 (#000023)");
+        assert_eq!(part1(&square), 9);
+        assert_eq!(part2(&square), 9);
+        let reversed = square.iter().rev().map(|&(p, _)| Step {
+            dir: point_mul(p.dir, -1),
+            meters: p.meters,
+        });
+        assert_eq!(lagoon_volume(&reversed.collect::<Vec<_>>()), Ok(9));
+    }
+
+    #[test]
+    fn unclosed_plan() {
+        let open = generator("R 2 (#000020)");
+        assert_eq!(
+            lagoon_volume(open.iter().map(|(p, _)| p)),
+            Err(ParseError::Unclosed([2, 0]))
+        );
+        assert_eq!(
+            vertices(open.iter().map(|(_, h)| h)),
+            Err(ParseError::Unclosed([2, 0]))
+        );
+    }
+}
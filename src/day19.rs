@@ -0,0 +1,489 @@
+use aoc::*;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use thiserror::Error;
+
+/// Ratings in `x, m, a, s` order.
+pub type Part = [u64; 4];
+/// A box of parts, one half-open rating range per category.
+pub type Ranges = [Range<u64>; 4];
+pub type Workflows = HashMap<String, Vec<Rule>>;
+
+type Data = (Workflows, Vec<Part>);
+type SolutionType = u64;
+
+pub const START: &str = "in";
+pub const RATINGS: Range<u64> = 1..4001;
+const CATEGORIES: &str = "xmas";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Accept,
+    Reject,
+    Workflow(String),
+}
+
+/// `category < value` or `category > value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub category: usize,
+    pub ordering: Ordering,
+    pub value: u64,
+}
+
+/// A rule without a condition is the fallthrough at the end of a workflow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub condition: Option<Condition>,
+    pub target: Target,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseError {
+    #[error("missing the blank line between workflows and parts")]
+    MissingParts,
+    #[error("workflow {0:?} is malformed")]
+    InvalidWorkflow(String),
+    #[error("rule {0:?} is malformed")]
+    InvalidRule(String),
+    #[error("part {0:?} is malformed")]
+    InvalidPart(String),
+    #[error("workflow {0:?} does not end in a fallthrough rule")]
+    MissingFallthrough(String),
+    #[error("there is no {START:?} workflow")]
+    MissingStart,
+    #[error("workflow {0:?} sends parts to unknown workflow {1:?}")]
+    UnknownWorkflow(String, String),
+    #[error("workflows {0:?} form a cycle")]
+    Cycle(Vec<String>),
+}
+
+/// Findings that don't stop evaluation but point at dead weight in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lint {
+    UnreachableWorkflow(String),
+    /// No part with ratings in [`RATINGS`] ever matches rule `rule` of the
+    /// workflow.
+    UnreachableRule {
+        workflow: String,
+        rule: usize,
+    },
+}
+
+impl std::str::FromStr for Target {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "A" => Target::Accept,
+            "R" => Target::Reject,
+            _ if !s.is_empty() && s.chars().all(|c| c.is_ascii_lowercase()) => {
+                Target::Workflow(s.to_string())
+            }
+            _ => return Err(ParseError::InvalidRule(s.to_string())),
+        })
+    }
+}
+
+impl std::str::FromStr for Rule {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((condition, target)) = s.split_once(':') else {
+            return Ok(Rule {
+                condition: None,
+                target: s.parse()?,
+            });
+        };
+        let err = || ParseError::InvalidRule(s.to_string());
+        let mut chars = condition.chars();
+        let category = chars
+            .next()
+            .and_then(|c| CATEGORIES.find(c))
+            .ok_or_else(err)?;
+        let ordering = match chars.next() {
+            Some('<') => Ordering::Less,
+            Some('>') => Ordering::Greater,
+            _ => return Err(err()),
+        };
+        let value = chars.as_str().parse().map_err(|_| err())?;
+        Ok(Rule {
+            condition: Some(Condition {
+                category,
+                ordering,
+                value,
+            }),
+            target: target.parse().map_err(|_| err())?,
+        })
+    }
+}
+
+impl Condition {
+    pub fn matches(&self, part: &Part) -> bool {
+        part[self.category].cmp(&self.value) == self.ordering
+    }
+
+    /// Splits `range` into the ratings that match and those that don't.
+    pub fn split(&self, range: &Range<u64>) -> (Range<u64>, Range<u64>) {
+        let clamp = |v: u64| v.clamp(range.start, range.end);
+        match self.ordering {
+            Ordering::Less => {
+                let mid = clamp(self.value);
+                (range.start..mid, mid..range.end)
+            }
+            _ => {
+                let mid = clamp(self.value + 1);
+                (mid..range.end, range.start..mid)
+            }
+        }
+    }
+}
+
+fn parse_workflow(line: &str) -> Result<(String, Vec<Rule>), ParseError> {
+    let (name, rules) = line
+        .strip_suffix('}')
+        .and_then(|l| l.split_once('{'))
+        .ok_or_else(|| ParseError::InvalidWorkflow(line.to_string()))?;
+    let rules = rules
+        .split(',')
+        .map(str::parse)
+        .collect::<Result<Vec<Rule>, _>>()?;
+    if rules.last().is_none_or(|r| r.condition.is_some()) {
+        return Err(ParseError::MissingFallthrough(name.to_string()));
+    }
+    Ok((name.to_string(), rules))
+}
+
+fn parse_part(line: &str) -> Result<Part, ParseError> {
+    let err = || ParseError::InvalidPart(line.to_string());
+    let ratings = line
+        .strip_prefix('{')
+        .and_then(|l| l.strip_suffix('}'))
+        .ok_or_else(err)?
+        .split(',')
+        .collect::<Vec<_>>();
+    if ratings.len() != 4 {
+        return Err(err());
+    }
+    let mut part = [0; 4];
+    for (rating, category) in ratings.into_iter().zip(CATEGORIES.chars()) {
+        part[CATEGORIES.find(category).unwrap()] = rating
+            .strip_prefix(category)
+            .and_then(|r| r.strip_prefix('='))
+            .and_then(|r| r.parse().ok())
+            .ok_or_else(err)?;
+    }
+    Ok(part)
+}
+
+/// Rejects workflow graphs that can't be evaluated: a missing start, targets
+/// that don't exist, and cycles.
+pub fn check(workflows: &Workflows) -> Result<(), ParseError> {
+    if !workflows.contains_key(START) {
+        return Err(ParseError::MissingStart);
+    }
+    let mut names = workflows.keys().collect::<Vec<_>>();
+    names.sort();
+    let index = names
+        .iter()
+        .enumerate()
+        .map(|(i, &name)| (name, i))
+        .collect::<HashMap<_, _>>();
+    let mut edges = vec![vec![]; names.len()];
+    for (from, &name) in names.iter().enumerate() {
+        for rule in &workflows[name] {
+            if let Target::Workflow(to) = &rule.target {
+                let &to = index
+                    .get(to)
+                    .ok_or_else(|| ParseError::UnknownWorkflow(name.clone(), to.clone()))?;
+                edges[from].push(to);
+            }
+        }
+    }
+    for component in strongly_connected_components(&edges) {
+        if component.len() > 1 || edges[component[0]].contains(&component[0]) {
+            let mut cycle = component
+                .into_iter()
+                .map(|i| names[i].clone())
+                .collect::<Vec<_>>();
+            cycle.sort();
+            return Err(ParseError::Cycle(cycle));
+        }
+    }
+    Ok(())
+}
+
+pub fn parse(input: &str) -> Result<Data, ParseError> {
+    let (workflows, parts) = input.split_once("\n\n").ok_or(ParseError::MissingParts)?;
+    let workflows = workflows
+        .lines()
+        .map(parse_workflow)
+        .collect::<Result<Workflows, _>>()?;
+    check(&workflows)?;
+    let parts = parts
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(parse_part)
+        .collect::<Result<_, _>>()?;
+    Ok((workflows, parts))
+}
+
+#[aoc_generator(day19)]
+pub fn generator(input: &str) -> Data {
+    parse(input).unwrap()
+}
+
+pub fn accepts(workflows: &Workflows, part: &Part) -> bool {
+    let mut name = START;
+    loop {
+        let rule = workflows[name]
+            .iter()
+            .find(|r| r.condition.as_ref().is_none_or(|c| c.matches(part)))
+            .unwrap();
+        match &rule.target {
+            Target::Accept => return true,
+            Target::Reject => return false,
+            Target::Workflow(next) => name = next,
+        }
+    }
+}
+
+fn is_empty(ranges: &Ranges) -> bool {
+    ranges.iter().any(|r| r.is_empty())
+}
+
+/// Sends the box `ranges` through the workflows starting at [`START`], cutting
+/// it at every condition. `visit` sees each rule with the non-empty box it
+/// matches. Returns the accepted boxes, which are disjoint.
+fn propagate(
+    workflows: &Workflows,
+    ranges: Ranges,
+    mut visit: impl FnMut(&str, usize, &Ranges),
+) -> Vec<Ranges> {
+    let mut accepted = vec![];
+    let mut stack = vec![(START, ranges)];
+    while let Some((name, mut rest)) = stack.pop() {
+        for (i, rule) in workflows[name].iter().enumerate() {
+            let matched = match &rule.condition {
+                Some(c) => {
+                    let (matched, remaining) = c.split(&rest[c.category]);
+                    let mut matched_box = rest.clone();
+                    matched_box[c.category] = matched;
+                    rest[c.category] = remaining;
+                    matched_box
+                }
+                None => std::mem::replace(&mut rest, [0..0, 0..0, 0..0, 0..0]),
+            };
+            if !is_empty(&matched) {
+                visit(name, i, &matched);
+                match &rule.target {
+                    Target::Accept => accepted.push(matched),
+                    Target::Reject => {}
+                    Target::Workflow(next) => stack.push((next, matched)),
+                }
+            }
+            if is_empty(&rest) {
+                break;
+            }
+        }
+    }
+    accepted
+}
+
+/// Number of parts inside `ranges` that end up accepted.
+pub fn accepted_combinations(workflows: &Workflows, ranges: Ranges) -> u64 {
+    propagate(workflows, ranges, |_, _, _| {})
+        .iter()
+        .map(|b| b.iter().map(|r| r.end - r.start).product::<u64>())
+        .sum()
+}
+
+/// Workflows and rules that no part with ratings in [`RATINGS`] can reach,
+/// sorted by workflow name.
+pub fn lint(workflows: &Workflows) -> Vec<Lint> {
+    let mut hits = BTreeMap::<&str, Vec<bool>>::new();
+    for (name, rules) in workflows {
+        hits.insert(name, vec![false; rules.len()]);
+    }
+    let full = [RATINGS, RATINGS, RATINGS, RATINGS];
+    propagate(workflows, full, |name, rule, _| {
+        hits.get_mut(name).unwrap()[rule] = true
+    });
+    let mut lints = vec![];
+    for (name, rules) in hits {
+        if rules.iter().all(|&hit| !hit) {
+            lints.push(Lint::UnreachableWorkflow(name.to_string()));
+            continue;
+        }
+        lints.extend(
+            rules
+                .iter()
+                .enumerate()
+                .filter(|(_, &hit)| !hit)
+                .map(|(rule, _)| Lint::UnreachableRule {
+                    workflow: name.to_string(),
+                    rule,
+                }),
+        );
+    }
+    lints
+}
+
+#[aoc(day19, part1)]
+pub fn part1((workflows, parts): &Data) -> SolutionType {
+    parts
+        .iter()
+        .filter(|p| accepts(workflows, p))
+        .map(|p| p.iter().sum::<u64>())
+        .sum()
+}
+
+#[aoc(day19, part2)]
+pub fn part2((workflows, _): &Data) -> SolutionType {
+    accepted_combinations(workflows, [RATINGS, RATINGS, RATINGS, RATINGS])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    const SAMPLE: &str = "px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+
+{x=787,m=2655,a=1222,s=2876}
+{x=1679,m=44,a=2067,s=496}
+{x=2036,m=264,a=79,s=2244}
+{x=2461,m=1339,a=466,s=291}
+{x=2127,m=1623,a=2188,s=1013}";
+
+    #[test]
+    fn part1_example() {
+        assert_eq!(part1(&generator(SAMPLE)), 19114);
+    }
+
+    #[test]
+    fn part2_example() {
+        assert_eq!(part2(&generator(SAMPLE)), 167409079868000);
+    }
+
+    #[test]
+    fn parse_rules() {
+        let (workflows, parts) = generator(SAMPLE);
+        assert_eq!(
+            workflows["px"],
+            vec![
+                Rule {
+                    condition: Some(Condition {
+                        category: 2,
+                        ordering: Ordering::Less,
+                        value: 2006
+                    }),
+                    target: Target::Workflow("qkq".to_string())
+                },
+                Rule {
+                    condition: Some(Condition {
+                        category: 1,
+                        ordering: Ordering::Greater,
+                        value: 2090
+                    }),
+                    target: Target::Accept
+                },
+                Rule {
+                    condition: None,
+                    target: Target::Workflow("rfg".to_string())
+                },
+            ]
+        );
+        assert_eq!(parts[0], [787, 2655, 1222, 2876]);
+
+        assert_eq!(parse("in{R}"), Err(ParseError::MissingParts));
+        assert_eq!(
+            parse("in{x>1:A}\n\n"),
+            Err(ParseError::MissingFallthrough("in".to_string()))
+        );
+        assert_eq!(
+            parse("in{y>1:A,R}\n\n"),
+            Err(ParseError::InvalidRule("y>1:A".to_string()))
+        );
+        assert_eq!(
+            parse("in{R}\n\n{x=1,m=2,a=3}"),
+            Err(ParseError::InvalidPart("{x=1,m=2,a=3}".to_string()))
+        );
+        assert_eq!(parse("ab{R}\n\n"), Err(ParseError::MissingStart));
+        assert_eq!(
+            parse("in{x>1:ab,R}\n\n"),
+            Err(ParseError::UnknownWorkflow(
+                "in".to_string(),
+                "ab".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        assert_eq!(
+            parse("in{x>1:ab,R}\nab{cd}\ncd{m<5:A,ab}\n\n"),
+            Err(ParseError::Cycle(vec!["ab".to_string(), "cd".to_string()]))
+        );
+        assert_eq!(
+            parse("in{x>1:A,in}\n\n"),
+            Err(ParseError::Cycle(vec!["in".to_string()]))
+        );
+    }
+
+    #[test]
+    fn unreachable() {
+        assert_eq!(lint(&generator(SAMPLE).0), vec![]);
+        let (workflows, _) =
+            generator("in{x>10:A,x>20:R,ab}\nab{x<5:cd,x<3:R,A}\ncd{A}\nef{R}\n\n");
+        assert_eq!(
+            lint(&workflows),
+            vec![
+                Lint::UnreachableRule {
+                    workflow: "ab".to_string(),
+                    rule: 1
+                },
+                Lint::UnreachableWorkflow("ef".to_string()),
+                Lint::UnreachableRule {
+                    workflow: "in".to_string(),
+                    rule: 1
+                },
+            ]
+        );
+        let (workflows, _) = generator("in{x<10:ab,R}\nab{x>20:A,R}\n\n");
+        assert_eq!(
+            lint(&workflows),
+            vec![Lint::UnreachableRule {
+                workflow: "ab".to_string(),
+                rule: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn ranges_match_parts() {
+        let (workflows, _) = generator(SAMPLE);
+        let ranges = [1410..1420, 2085..2095, 2001..2011, 1345..1355];
+        let brute_force = ranges
+            .iter()
+            .cloned()
+            .multi_cartesian_product()
+            .filter(|p| accepts(&workflows, &[p[0], p[1], p[2], p[3]]))
+            .count();
+        assert_eq!(
+            accepted_combinations(&workflows, ranges),
+            brute_force as u64
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::{HashMap, VecDeque};
+use thiserror::Error;
+
+type SolutionType = u64;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Pulse {
+    Low,
+    High,
+}
+
+/// Flips on every low pulse and reports its new state; ignores high pulses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlipFlop {
+    on: bool,
+}
+
+/// Remembers the last pulse from each input and sends low only once all of
+/// them were high.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Conjunction {
+    memory: Vec<Pulse>,
+    high: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Module {
+    Broadcaster,
+    FlipFlop(FlipFlop),
+    Conjunction(Conjunction),
+    /// A name that only ever receives pulses, like `output` or `rx`.
+    Sink,
+}
+
+impl FlipFlop {
+    fn receive(&mut self, pulse: Pulse) -> Option<Pulse> {
+        if pulse == Pulse::High {
+            return None;
+        }
+        self.on = !self.on;
+        Some(if self.on { Pulse::High } else { Pulse::Low })
+    }
+}
+
+impl Conjunction {
+    /// `input` is the position of the sender among this module's inputs.
+    fn receive(&mut self, input: usize, pulse: Pulse) -> Option<Pulse> {
+        match (self.memory[input], pulse) {
+            (Pulse::Low, Pulse::High) => self.high += 1,
+            (Pulse::High, Pulse::Low) => self.high -= 1,
+            _ => {}
+        }
+        self.memory[input] = pulse;
+        Some(if self.high == self.memory.len() {
+            Pulse::Low
+        } else {
+            Pulse::High
+        })
+    }
+}
+
+impl Module {
+    fn receive(&mut self, input: usize, pulse: Pulse) -> Option<Pulse> {
+        match self {
+            Module::Broadcaster => Some(pulse),
+            Module::FlipFlop(f) => f.receive(pulse),
+            Module::Conjunction(c) => c.receive(input, pulse),
+            Module::Sink => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum NetworkError {
+    #[error("line {0:?} is not of the form `%name -> a, b`")]
+    Malformed(String),
+    #[error("module {0:?} is defined twice")]
+    Duplicate(String),
+    #[error("there is no broadcaster")]
+    MissingBroadcaster,
+    #[error("there is no module {0:?}")]
+    UnknownModule(String),
+    #[error("{0:?} is not fed by exactly one conjunction")]
+    NotFedByConjunction(String),
+    #[error("input {0:?} of the final conjunction shows no cycle within {1} presses")]
+    NoCycle(String, u64),
+    #[error("input {0:?} of the final conjunction first fires at {1}, not at its period {2}")]
+    OffsetCycle(String, u64, u64),
+}
+
+/// Sent and delivered pulses of one or more button presses.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub low: u64,
+    pub high: u64,
+}
+
+/// Modules interned by name, wired with their output and input lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    modules: Vec<Module>,
+    outputs: Vec<Vec<usize>>,
+    inputs: Vec<Vec<usize>>,
+    /// Position of each output edge's sender in the receiver's input list.
+    slots: Vec<Vec<usize>>,
+    broadcaster: usize,
+}
+
+impl Network {
+    pub fn parse(input: &str) -> Result<Network, NetworkError> {
+        let mut declared = vec![];
+        for line in input.lines().filter(|l| !l.is_empty()) {
+            let (name, outputs) = line
+                .split_once(" -> ")
+                .ok_or_else(|| NetworkError::Malformed(line.to_string()))?;
+            let (module, name) = match name.as_bytes().first() {
+                Some(b'%') => (Module::FlipFlop(FlipFlop::default()), &name[1..]),
+                Some(b'&') => (Module::Conjunction(Conjunction::default()), &name[1..]),
+                _ if name == "broadcaster" => (Module::Broadcaster, name),
+                _ => return Err(NetworkError::Malformed(line.to_string())),
+            };
+            declared.push((name, module, outputs.split(", ").collect::<Vec<_>>()));
+        }
+
+        let mut network = Network {
+            names: vec![],
+            index: HashMap::new(),
+            modules: vec![],
+            outputs: vec![],
+            inputs: vec![],
+            slots: vec![],
+            broadcaster: 0,
+        };
+        for (name, module, _) in &declared {
+            if network.index.contains_key(*name) {
+                return Err(NetworkError::Duplicate(name.to_string()));
+            }
+            network.intern(name);
+            *network.modules.last_mut().unwrap() = module.clone();
+        }
+        network.broadcaster = *network
+            .index
+            .get("broadcaster")
+            .ok_or(NetworkError::MissingBroadcaster)?;
+        for (name, _, outputs) in &declared {
+            let from = network.index[*name];
+            for output in outputs {
+                let to = network.intern(output);
+                network.slots[from].push(network.inputs[to].len());
+                network.outputs[from].push(to);
+                network.inputs[to].push(from);
+            }
+        }
+        for (module, inputs) in network.modules.iter_mut().zip(&network.inputs) {
+            if let Module::Conjunction(c) = module {
+                c.memory = vec![Pulse::Low; inputs.len()];
+            }
+        }
+        Ok(network)
+    }
+
+    fn intern(&mut self, name: &str) -> usize {
+        if let Some(&i) = self.index.get(name) {
+            return i;
+        }
+        self.index.insert(name.to_string(), self.names.len());
+        self.names.push(name.to_string());
+        self.modules.push(Module::Sink);
+        self.outputs.push(vec![]);
+        self.inputs.push(vec![]);
+        self.slots.push(vec![]);
+        self.names.len() - 1
+    }
+
+    /// The name behind an index handed to a [`Network::press`] observer.
+    pub fn name(&self, module: usize) -> &str {
+        &self.names[module]
+    }
+
+    fn lookup(&self, name: &str) -> Result<usize, NetworkError> {
+        self.index
+            .get(name)
+            .copied()
+            .ok_or_else(|| NetworkError::UnknownModule(name.to_string()))
+    }
+
+    /// Pushes the button once, delivering pulses in the order they were sent.
+    /// `observe` sees every pulse as `(from, to, pulse)` on delivery.
+    pub fn press(&mut self, mut observe: impl FnMut(usize, usize, Pulse)) -> Counts {
+        let mut counts = Counts::default();
+        // The button's own low pulse to the broadcaster.
+        counts.low += 1;
+        let mut bus = VecDeque::new();
+        if let Some(pulse) = self.modules[self.broadcaster].receive(0, Pulse::Low) {
+            bus.push_back((self.broadcaster, pulse));
+        }
+        while let Some((from, pulse)) = bus.pop_front() {
+            for (&to, &slot) in self.outputs[from].iter().zip(&self.slots[from]) {
+                match pulse {
+                    Pulse::Low => counts.low += 1,
+                    Pulse::High => counts.high += 1,
+                }
+                observe(from, to, pulse);
+                if let Some(next) = self.modules[to].receive(slot, pulse) {
+                    bus.push_back((to, next));
+                }
+            }
+        }
+        counts
+    }
+
+    /// Total pulses over `presses` presses.
+    pub fn press_many(&mut self, presses: usize) -> Counts {
+        (0..presses).fold(Counts::default(), |acc, _| {
+            let c = self.press(|_, _, _| {});
+            Counts {
+                low: acc.low + c.low,
+                high: acc.high + c.high,
+            }
+        })
+    }
+
+    /// Presses until `target` first receives a low pulse. Only viable on
+    /// small networks; kept as the reference for [`Network::presses_until_low`].
+    pub fn presses_until_low_bruteforce(&mut self, target: &str, limit: u64) -> Option<u64> {
+        let target = self.lookup(target).ok()?;
+        (1..=limit).find(|_| {
+            let mut hit = false;
+            self.press(|_, to, pulse| hit |= to == target && pulse == Pulse::Low);
+            hit
+        })
+    }
+
+    /// Presses until `target` first receives a low pulse, for networks where
+    /// `target` hangs off a single conjunction whose inputs are independent
+    /// counters. Each input sends that conjunction a high pulse every `p_i`
+    /// presses, so the answer is the LCM of the `p_i`. Every period is
+    /// confirmed by three evenly spaced hits starting at press `p_i`.
+    pub fn presses_until_low(&mut self, target: &str, limit: u64) -> Result<u64, NetworkError> {
+        let target_idx = self.lookup(target)?;
+        let feeder = match self.inputs[target_idx].as_slice() {
+            &[f] if matches!(self.modules[f], Module::Conjunction(_)) => f,
+            _ => return Err(NetworkError::NotFedByConjunction(target.to_string())),
+        };
+        let sources = self.inputs[feeder].clone();
+        let mut hits = vec![vec![]; sources.len()];
+        for press in 1..=limit {
+            self.press(|from, to, pulse| {
+                if to == feeder && pulse == Pulse::High {
+                    let slot = sources.iter().position(|&s| s == from).unwrap();
+                    if hits[slot].last() != Some(&press) {
+                        hits[slot].push(press);
+                    }
+                }
+            });
+            if hits.iter().all(|h| h.len() >= 3) {
+                break;
+            }
+        }
+
+        let mut answer = 1;
+        for (&source, hits) in sources.iter().zip(&hits) {
+            let name = || self.name(source).to_string();
+            let &[first, second, third, ..] = hits.as_slice() else {
+                return Err(NetworkError::NoCycle(name(), limit));
+            };
+            let period = second - first;
+            if third - second != period {
+                return Err(NetworkError::NoCycle(name(), limit));
+            }
+            if first != period {
+                return Err(NetworkError::OffsetCycle(name(), first, period));
+            }
+            answer = num::integer::lcm(answer, period);
+        }
+        Ok(answer)
+    }
+}
+
+#[aoc_generator(day20)]
+pub fn generator(input: &str) -> Network {
+    Network::parse(input).unwrap()
+}
+
+#[aoc(day20, part1)]
+pub fn part1(input: &Network) -> SolutionType {
+    let counts = input.clone().press_many(1000);
+    counts.low * counts.high
+}
+
+#[aoc(day20, part2)]
+pub fn part2(input: &Network) -> SolutionType {
+    input.clone().presses_until_low("rx", 100_000).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    const SAMPLE: &str = "broadcaster -> a, b, c
+%a -> b
+%b -> c
+%c -> inv
+&inv -> a";
+
+    const SAMPLE2: &str = "broadcaster -> a
+%a -> inv, con
+&inv -> b
+%b -> con
+&con -> output";
+
+    #[test]
+    fn part1_example() {
+        assert_eq!(part1(&generator(SAMPLE)), 32000000);
+        assert_eq!(part1(&generator(SAMPLE2)), 11687500);
+    }
+
+    #[test]
+    fn single_press() {
+        let mut network = generator(SAMPLE);
+        let names = network.names.clone();
+        let mut log = vec![];
+        let counts = network.press(|from, to, pulse| {
+            log.push(format!(
+                "{} -{}-> {}",
+                names[from],
+                if pulse == Pulse::High { "high" } else { "low" },
+                names[to]
+            ))
+        });
+        assert_eq!(
+            log,
+            [
+                "broadcaster -low-> a",
+                "broadcaster -low-> b",
+                "broadcaster -low-> c",
+                "a -high-> b",
+                "b -high-> c",
+                "c -high-> inv",
+                "inv -low-> a",
+                "a -low-> b",
+                "b -low-> c",
+                "c -low-> inv",
+                "inv -high-> a",
+            ]
+        );
+        assert_eq!(counts, Counts { low: 8, high: 4 });
+        assert_eq!(network, generator(SAMPLE));
+    }
+
+    /// A miniature of the real input: one counter per period, each a chain of
+    /// flip-flops wired to a conjunction that resets it on reaching `period`.
+    fn counters(periods: &[u64]) -> String {
+        let mut lines = vec![];
+        let mut starts = vec![];
+        for (n, &period) in periods.iter().enumerate() {
+            let bits = 64 - period.leading_zeros() as usize;
+            let bit = |i: usize| format!("b{n}x{i}");
+            let (conj, inv) = (format!("c{n}"), format!("i{n}"));
+            for i in 0..bits {
+                let mut outputs = vec![];
+                if i + 1 < bits {
+                    outputs.push(bit(i + 1));
+                }
+                if period >> i & 1 == 1 {
+                    outputs.push(conj.clone());
+                }
+                lines.push(format!("%{} -> {}", bit(i), outputs.join(", ")));
+            }
+            let resets = (0..bits)
+                .filter(|&i| i == 0 || period >> i & 1 == 0)
+                .map(bit)
+                .chain([inv.clone()])
+                .join(", ");
+            lines.push(format!("&{conj} -> {resets}"));
+            lines.push(format!("&{inv} -> fin"));
+            starts.push(bit(0));
+        }
+        lines.push(format!("broadcaster -> {}", starts.join(", ")));
+        lines.push("&fin -> rx".to_string());
+        lines.join("\n")
+    }
+
+    #[test]
+    fn part2_counters() {
+        for periods in [vec![5, 7], vec![5, 7, 11], vec![13, 9, 15]] {
+            let network = generator(&counters(&periods));
+            let expected = periods.iter().fold(1, |a, &p| num::integer::lcm(a, p));
+            assert_eq!(part2(&network), expected);
+            assert_eq!(
+                network.clone().presses_until_low_bruteforce("rx", 10_000),
+                Some(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn part2_errors() {
+        let mut network = generator(SAMPLE2);
+        assert_eq!(
+            network.presses_until_low("rx", 10),
+            Err(NetworkError::UnknownModule("rx".to_string()))
+        );
+        assert_eq!(
+            network.presses_until_low("con", 10),
+            Err(NetworkError::NotFedByConjunction("con".to_string()))
+        );
+        let mut network = generator(&counters(&[5, 7]));
+        assert_eq!(
+            network.presses_until_low("rx", 12),
+            Err(NetworkError::NoCycle("i0".to_string(), 12))
+        );
+        assert_eq!(
+            Network::parse("%a -> b"),
+            Err(NetworkError::MissingBroadcaster)
+        );
+        assert_eq!(
+            Network::parse("broadcaster -> a\n%a -> b\n&a -> b"),
+            Err(NetworkError::Duplicate("a".to_string()))
+        );
+        assert_eq!(
+            Network::parse("broadcaster a"),
+            Err(NetworkError::Malformed("broadcaster a".to_string()))
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use aoc::*;
+use std::collections::{HashSet, VecDeque};
+use thiserror::Error;
+
+type SolutionType = u64;
+
+pub const PART1_STEPS: u64 = 64;
+pub const PART2_STEPS: u64 = 26501365;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum GardenError {
+    #[error("the map is {0}x{1}, but extrapolating needs a square map of odd size")]
+    NotSquare(usize, usize),
+    #[error("the start {0:?} is not in the centre of the map")]
+    StartNotCentred(Point),
+    #[error("row {0} through the start has rocks, so tiles are not reached in straight lines")]
+    BlockedRow(usize),
+    #[error("column {0} through the start has rocks, so tiles are not reached in straight lines")]
+    BlockedColumn(usize),
+    #[error("{0} steps is not half the map size plus a multiple of its size {1}")]
+    UnalignedSteps(u64, usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Garden {
+    rocks: Vec<Vec<bool>>,
+    start: Point,
+}
+
+#[aoc_generator(day21)]
+pub fn generator(input: &str) -> Garden {
+    let grid = parse_grid(input.lines().collect::<Vec<_>>().as_slice());
+    let start = grid
+        .iter()
+        .enumerate()
+        .find_map(|(y, row)| {
+            row.iter()
+                .position(|&c| c == 'S')
+                .map(|x| [x as i64, y as i64])
+        })
+        .unwrap();
+    let rocks = grid
+        .into_iter()
+        .map(|row| row.into_iter().map(|c| c == '#').collect())
+        .collect();
+    Garden { rocks, start }
+}
+
+impl Garden {
+    fn width(&self) -> usize {
+        self.rocks.first().map_or(0, |row| row.len())
+    }
+
+    fn height(&self) -> usize {
+        self.rocks.len()
+    }
+
+    /// Whether `[x, y]` is a rock on the map repeated infinitely in every
+    /// direction.
+    fn is_rock(&self, [x, y]: Point) -> bool {
+        let (w, h) = (self.width() as i64, self.height() as i64);
+        self.rocks[y.rem_euclid(h) as usize][x.rem_euclid(w) as usize]
+    }
+
+    /// Plots reachable in exactly `steps` steps: those at most `steps` away
+    /// with the same parity, as the elf can always step back and forth. With
+    /// `tiled` the map repeats; otherwise its edge is a wall.
+    pub fn reachable(&self, steps: u64, tiled: bool) -> u64 {
+        let (w, h) = (self.width() as i64, self.height() as i64);
+        let mut seen = HashSet::from([self.start]);
+        let mut queue = VecDeque::from([(self.start, 0)]);
+        let mut count = 0;
+        while let Some((pos, dist)) = queue.pop_front() {
+            if dist % 2 == steps % 2 {
+                count += 1;
+            }
+            if dist == steps {
+                continue;
+            }
+            for next in neighbors(pos) {
+                let [x, y] = next;
+                let inside = (0..w).contains(&x) && (0..h).contains(&y);
+                if (tiled || inside) && !self.is_rock(next) && seen.insert(next) {
+                    queue.push_back((next, dist + 1));
+                }
+            }
+        }
+        count
+    }
+
+    /// Checks what makes the count a quadratic in the number of map widths
+    /// walked: a square map of odd size with the start in its centre, and
+    /// clear lines from the start to every edge, so each copy of the map is
+    /// first entered at a predictable time and place.
+    pub fn check_tiling(&self) -> Result<(), GardenError> {
+        let size = self.width();
+        if size != self.height() || size.is_multiple_of(2) {
+            return Err(GardenError::NotSquare(self.width(), self.height()));
+        }
+        let centre = (size / 2) as i64;
+        if self.start != [centre, centre] {
+            return Err(GardenError::StartNotCentred(self.start));
+        }
+        let centre = centre as usize;
+        if self.rocks[centre].iter().any(|&r| r) {
+            return Err(GardenError::BlockedRow(centre));
+        }
+        if self.rocks.iter().any(|row| row[centre]) {
+            return Err(GardenError::BlockedColumn(centre));
+        }
+        Ok(())
+    }
+
+    /// Plots reachable in `steps` on the tiled map, for `steps = size / 2 +
+    /// k * size`. Counts `k = 0, 1, 2` by breadth-first search and
+    /// extrapolates the quadratic through them to `k`.
+    pub fn reachable_extrapolated(&self, steps: u64) -> Result<u64, GardenError> {
+        self.check_tiling()?;
+        let size = self.width() as u64;
+        let half = size / 2;
+        if steps < half || !(steps - half).is_multiple_of(size) {
+            return Err(GardenError::UnalignedSteps(steps, size as usize));
+        }
+        let samples = (0..3)
+            .map(|k| self.reachable(half + k * size, true) as i64)
+            .collect::<Vec<_>>();
+        Ok(extrapolate(&samples, ((steps - half) / size) as i64) as u64)
+    }
+}
+
+#[aoc(day21, part1)]
+pub fn part1(input: &Garden) -> SolutionType {
+    input.reachable(PART1_STEPS, false)
+}
+
+#[aoc(day21, part2)]
+pub fn part2(input: &Garden) -> SolutionType {
+    input.reachable_extrapolated(PART2_STEPS).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "...........
+.....###.#.
+.###.##..#.
+..#.#...#..
+....#.#....
+.##..S####.
+.##..#...#.
+.......##..
+.##.#.####.
+.##..##.##.
+...........";
+
+    #[test]
+    fn part1_example() {
+        assert_eq!(generator(SAMPLE).reachable(6, false), 16);
+    }
+
+    #[test]
+    fn tiled_example() {
+        let garden = generator(SAMPLE);
+        for (steps, plots) in [(6, 16), (10, 50), (50, 1594), (100, 6536)] {
+            assert_eq!(garden.reachable(steps, true), plots);
+        }
+    }
+
+    #[test]
+    fn tiling_assumptions() {
+        let garden = generator(SAMPLE);
+        assert_eq!(garden.check_tiling(), Err(GardenError::BlockedRow(5)));
+        assert_eq!(
+            garden.reachable_extrapolated(PART2_STEPS),
+            Err(GardenError::BlockedRow(5))
+        );
+
+        let cleared = SAMPLE.replace(".##..S####.", ".....S.....");
+        assert_eq!(
+            generator(&cleared).check_tiling(),
+            Err(GardenError::BlockedColumn(5))
+        );
+        assert_eq!(
+            generator("...\n.S.\n...\n...").check_tiling(),
+            Err(GardenError::NotSquare(3, 4))
+        );
+        assert_eq!(
+            generator("...\n..S\n...").check_tiling(),
+            Err(GardenError::StartNotCentred([2, 1]))
+        );
+        assert_eq!(
+            generator("...\n.S.\n...").reachable_extrapolated(11),
+            Err(GardenError::UnalignedSteps(11, 3))
+        );
+    }
+
+    #[test]
+    fn extrapolated_matches_bfs() {
+        // The sample with its centre row and column cleared.
+        let garden = generator(
+            "...........
+......##.#.
+.###..#..#.
+..#.#...#..
+....#.#....
+.....S.....
+.##......#.
+.......##..
+.##.#.####.
+.##...#.##.
+...........",
+        );
+        garden.check_tiling().unwrap();
+        for k in 3..6 {
+            let steps = 5 + 11 * k;
+            assert_eq!(
+                garden.reachable_extrapolated(steps),
+                Ok(garden.reachable(steps, true))
+            );
+        }
+    }
+}
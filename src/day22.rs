@@ -0,0 +1,277 @@
+use aoc::*;
+use itertools::Itertools;
+
+type SolutionType = usize;
+
+/// An axis-aligned brick spanning `min..=max` in every coordinate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Brick {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Brick {
+    fn footprint(&self) -> impl Iterator<Item = (i64, i64)> {
+        (self.min[0]..=self.max[0]).cartesian_product(self.min[1]..=self.max[1])
+    }
+
+    fn moved_to(&self, z: i64) -> Brick {
+        let offset = [0, 0, z - self.min[2]];
+        Brick {
+            min: vec_add(self.min, offset),
+            max: vec_add(self.max, offset),
+        }
+    }
+}
+
+/// Bricks after settling, with who rests on whom. Bricks are kept in the
+/// order they were dropped, so each comes after the ones it rests on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stack {
+    pub bricks: Vec<Brick>,
+    /// Bricks resting directly on brick `i`.
+    pub supports: Vec<Vec<usize>>,
+    /// Bricks brick `i` rests directly on; empty for bricks on the ground.
+    pub supported_by: Vec<Vec<usize>>,
+}
+
+/// Tallest point and topmost brick of every column of a height map.
+struct HeightMap {
+    width: i64,
+    columns: Vec<(i64, Option<usize>)>,
+}
+
+impl HeightMap {
+    fn new(bricks: &[Brick]) -> HeightMap {
+        let width = bricks.iter().map(|b| b.max[0] + 1).max().unwrap_or(0);
+        let depth = bricks.iter().map(|b| b.max[1] + 1).max().unwrap_or(0);
+        HeightMap {
+            width,
+            columns: vec![(0, None); (width * depth) as usize],
+        }
+    }
+
+    fn column(&mut self, (x, y): (i64, i64)) -> &mut (i64, Option<usize>) {
+        &mut self.columns[(y * self.width + x) as usize]
+    }
+}
+
+impl Stack {
+    /// Lets every brick fall until it rests on the ground at `z = 1` or on
+    /// another brick. Dropping bricks in order of their bottom face means
+    /// everything below a brick has already settled.
+    pub fn settle(bricks: &[Brick]) -> Stack {
+        let mut bricks = bricks.to_vec();
+        bricks.sort_by_key(|b| b.min[2]);
+        let mut heights = HeightMap::new(&bricks);
+        let mut supports = vec![vec![]; bricks.len()];
+        let mut supported_by = vec![vec![]; bricks.len()];
+
+        for i in 0..bricks.len() {
+            let top = bricks[i]
+                .footprint()
+                .map(|c| heights.column(c).0)
+                .max()
+                .unwrap();
+            bricks[i] = bricks[i].moved_to(top + 1);
+            for c in bricks[i].footprint() {
+                let column = heights.column(c);
+                if let (true, Some(below)) = (column.0 == top, column.1) {
+                    if !supported_by[i].contains(&below) {
+                        supported_by[i].push(below);
+                        supports[below].push(i);
+                    }
+                }
+                *column = (bricks[i].max[2], Some(i));
+            }
+        }
+        Stack {
+            bricks,
+            supports,
+            supported_by,
+        }
+    }
+
+    /// Bricks that can be taken out without anything else falling: every
+    /// brick they hold up rests on another one too.
+    pub fn disintegrable(&self) -> usize {
+        self.supports
+            .iter()
+            .filter(|above| above.iter().all(|&a| self.supported_by[a].len() > 1))
+            .count()
+    }
+
+    /// How many other bricks fall when brick `i` is taken out, for every `i`.
+    ///
+    /// A brick falls exactly when every path down to the ground passes
+    /// through `i`, i.e. when `i` dominates it in the support graph rooted at
+    /// the ground. Bricks are already in topological order, so each one's
+    /// immediate dominator is the nearest common dominator of its supports,
+    /// and the fall count of `i` is the size of its dominator subtree.
+    pub fn chain_reactions(&self) -> Vec<usize> {
+        // Index `n` is the ground.
+        let ground = self.bricks.len();
+        let mut idom = vec![ground; ground + 1];
+        let mut depth = vec![0; ground + 1];
+        let common = |idom: &[usize], depth: &[usize], mut a: usize, mut b: usize| {
+            while a != b {
+                if depth[a] < depth[b] {
+                    b = idom[b];
+                } else {
+                    a = idom[a];
+                }
+            }
+            a
+        };
+        for i in 0..ground {
+            idom[i] = self.supported_by[i]
+                .iter()
+                .copied()
+                .reduce(|a, b| common(&idom, &depth, a, b))
+                .unwrap_or(ground);
+            depth[i] = depth[idom[i]] + 1;
+        }
+
+        // Children come after their dominators, so a reverse sweep adds up
+        // whole subtrees.
+        let mut subtree = vec![1; ground + 1];
+        for i in (0..ground).rev() {
+            subtree[idom[i]] += subtree[i];
+        }
+        subtree.truncate(ground);
+        subtree.iter().map(|s| s - 1).collect()
+    }
+}
+
+#[aoc_generator(day22)]
+pub fn generator(input: &str) -> Stack {
+    let corner = |c: &str| -> Vec3 {
+        let (x, y, z) = c
+            .split(',')
+            .map(|v| v.parse().unwrap())
+            .collect_tuple()
+            .unwrap();
+        [x, y, z]
+    };
+    let bricks = input
+        .lines()
+        .map(|line| {
+            let (a, b) = line.split_once('~').unwrap();
+            let (a, b) = (corner(a), corner(b));
+            Brick {
+                min: [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+                max: [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+            }
+        })
+        .collect::<Vec<_>>();
+    Stack::settle(&bricks)
+}
+
+#[aoc(day22, part1)]
+pub fn part1(input: &Stack) -> SolutionType {
+    input.disintegrable()
+}
+
+#[aoc(day22, part2)]
+pub fn part2(input: &Stack) -> SolutionType {
+    input.chain_reactions().iter().sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    const SAMPLE: &str = "1,0,1~1,2,1
+0,0,2~2,0,2
+0,2,3~2,2,3
+0,0,4~0,2,4
+2,0,5~2,2,5
+0,1,6~2,1,6
+1,1,8~1,1,9";
+
+    #[test]
+    fn part1_example() {
+        assert_eq!(part1(&generator(SAMPLE)), 5);
+    }
+
+    #[test]
+    fn part2_example() {
+        assert_eq!(part2(&generator(SAMPLE)), 7);
+        assert_eq!(generator(SAMPLE).chain_reactions(), [6, 0, 0, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn settling() {
+        let stack = generator(SAMPLE);
+        assert_eq!(stack.bricks[6].min, [1, 1, 5]);
+        assert_eq!(stack.bricks[6].max, [1, 1, 6]);
+        assert_eq!(stack.supported_by[3], [1, 2]);
+        assert_eq!(stack.supports[0], [1, 2]);
+        assert_eq!(
+            generator("2,2,3~0,0,2").bricks,
+            [Brick {
+                min: [0, 0, 1],
+                max: [2, 2, 2]
+            }]
+        );
+    }
+
+    /// Drops the settled bricks again without brick `removed` and counts the
+    /// ones that end up lower.
+    fn falls_without(stack: &Stack, removed: usize) -> usize {
+        let mut heights = HashMap::new();
+        let mut falls = 0;
+        for (i, brick) in stack.bricks.iter().enumerate() {
+            if i == removed {
+                continue;
+            }
+            let top = brick
+                .footprint()
+                .map(|c| *heights.get(&c).unwrap_or(&0))
+                .max()
+                .unwrap();
+            let brick = brick.moved_to(top + 1);
+            if brick != stack.bricks[i] {
+                falls += 1;
+            }
+            for c in brick.footprint() {
+                heights.insert(c, brick.max[2]);
+            }
+        }
+        falls
+    }
+
+    #[test]
+    fn chain_reactions_match_resettling() {
+        let mut rng = SmallRng::seed_from_u64(11);
+        let mut next = |n: i64| rng.gen_range(0..n);
+        let mut inputs = vec![SAMPLE.to_string()];
+        for _ in 0..5 {
+            let bricks = (0..60)
+                .map(|z| {
+                    let (x, y, len) = (next(5), next(5), next(4));
+                    match next(3) {
+                        0 => format!("{x},{y},{z}~{},{y},{z}", (x + len).min(4)),
+                        1 => format!("{x},{y},{z}~{x},{},{z}", (y + len).min(4)),
+                        _ => format!("{x},{y},{}~{x},{y},{}", z * 3, z * 3 + len),
+                    }
+                })
+                .join("\n");
+            inputs.push(bricks);
+        }
+        for input in inputs {
+            let stack = generator(&input);
+            let expected = (0..stack.bricks.len())
+                .map(|i| falls_without(&stack, i))
+                .collect::<Vec<_>>();
+            assert_eq!(stack.chain_reactions(), expected);
+            assert_eq!(
+                stack.disintegrable(),
+                expected.iter().filter(|&&f| f == 0).count()
+            );
+        }
+    }
+}
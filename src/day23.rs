@@ -0,0 +1,165 @@
+use aoc::*;
+
+type Data = Vec<Vec<char>>;
+type SolutionType = usize;
+
+/// Weighted corridors between junctions; index 0 is the start, 1 the goal.
+type Graph = Vec<Vec<(usize, usize)>>;
+
+#[aoc_generator(day23)]
+pub fn generator(input: &str) -> Data {
+    parse_grid(input.lines().collect::<Vec<_>>().as_slice())
+}
+
+fn slope(c: char) -> Option<Point> {
+    match c {
+        '^' => Some(NORTH),
+        '>' => Some(EAST),
+        'v' => Some(SOUTH),
+        '<' => Some(WEST),
+        _ => None,
+    }
+}
+
+fn is_open(grid: &Data, p: Point) -> bool {
+    grid.get_value(p).is_some_and(|c| c != '#')
+}
+
+/// The junction graph of the trail map: the gap in the top row, the gap in
+/// the bottom row, and every cell with three or more open neighbours. With
+/// `slippery` slopes can only be left downhill.
+pub fn compress(grid: &Data, slippery: bool) -> Graph {
+    let gap =
+        |row: &Vec<char>, y: usize| [row.iter().position(|&c| c == '.').unwrap() as i64, y as i64];
+    let last = grid.len() - 1;
+    let mut junctions = vec![gap(&grid[0], 0), gap(&grid[last], last)];
+    junctions.extend(
+        grid.points().filter(|&p| {
+            is_open(grid, p) && neighbors(p).filter(|&n| is_open(grid, n)).count() > 2
+        }),
+    );
+    junction_graph(&junctions, |p| {
+        let forced = grid.get_value(p).and_then(slope).filter(|_| slippery);
+        neighbors(p)
+            .filter(|&n| is_open(grid, n))
+            .filter(move |&n| forced.is_none_or(|dir| n == point_add(p, dir)))
+            .collect::<Vec<_>>()
+    })
+}
+
+/// Length of the longest path from junction 0 to junction 1 that visits no
+/// junction twice, by depth-first search over a bitmask of visited junctions.
+pub fn longest_path(graph: &Graph) -> Option<usize> {
+    assert!(
+        graph.len() <= 64,
+        "{} junctions do not fit a u64",
+        graph.len()
+    );
+    // Once at the goal's only neighbour, walking on anywhere else can never
+    // come back to the goal, so go straight there.
+    let last_hop = match graph
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.iter().any(|&(to, _)| to == 1))
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        &[(j, edges)] => Some((j, edges.iter().find(|&&(to, _)| to == 1).unwrap().1)),
+        _ => None,
+    };
+    let mut best = None;
+    let mut stack = vec![(0, 1u64, 0)];
+    while let Some((node, visited, len)) = stack.pop() {
+        if node == 1 {
+            best = best.max(Some(len));
+            continue;
+        }
+        if let Some((j, hop)) = last_hop.filter(|&(j, _)| j == node) {
+            stack.push((1, visited | 1 << 1 | 1 << j, len + hop));
+            continue;
+        }
+        for &(next, weight) in &graph[node] {
+            if visited & 1 << next == 0 {
+                stack.push((next, visited | 1 << next, len + weight));
+            }
+        }
+    }
+    best
+}
+
+#[aoc(day23, part1)]
+pub fn part1(input: &Data) -> SolutionType {
+    longest_path(&compress(input, true)).unwrap()
+}
+
+#[aoc(day23, part2)]
+pub fn part2(input: &Data) -> SolutionType {
+    longest_path(&compress(input, false)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "#.#####################
+#.......#########...###
+#######.#########.#.###
+###.....#.>.>.###.#.###
+###v#####.#v#.###.#.###
+###.>...#.#.#.....#...#
+###v###.#.#.#########.#
+###...#.#.#.......#...#
+#####.#.#.#######.#.###
+#.....#.#.#.......#...#
+#.#####.#.#.#########v#
+#.#...#...#...###...>.#
+#.#.#v#######v###.###v#
+#...#.>.#...>.>.#.###.#
+#####v#.#.###v#.#.###.#
+#.....#...#...#.#.#...#
+#.#########.###.#.#.###
+#...###...#...#...#.###
+###.###.#.###v#####v###
+#...#...#.#.>.>.#.>.###
+#.###.###.#.###.#.#v###
+#.....###...###...#...#
+#####################.#";
+
+    #[test]
+    fn part1_example() {
+        assert_eq!(part1(&generator(SAMPLE)), 94);
+    }
+
+    #[test]
+    fn part2_example() {
+        assert_eq!(part2(&generator(SAMPLE)), 154);
+    }
+
+    #[test]
+    fn compressed_graph() {
+        let grid = generator(SAMPLE);
+        let graph = compress(&grid, false);
+        assert_eq!(graph.len(), 9);
+        // The start's only corridor leads to the first junction at [3, 5].
+        assert_eq!(graph[0].len(), 1);
+        assert_eq!(graph[0][0].1, 15);
+        // Without slopes every corridor can be walked both ways.
+        for (from, edges) in graph.iter().enumerate() {
+            for &(to, len) in edges {
+                assert!(graph[to].contains(&(from, len)));
+            }
+        }
+        let slippery = compress(&grid, true);
+        assert_eq!(
+            slippery.iter().map(Vec::len).sum::<usize>() * 2,
+            graph.iter().map(Vec::len).sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn no_path() {
+        let grid = generator("#.###\n#.<.#\n###.#\n#.<.#\n#.###");
+        assert_eq!(longest_path(&compress(&grid, true)), None);
+        assert_eq!(longest_path(&compress(&grid, false)), Some(8));
+    }
+}
@@ -0,0 +1,225 @@
+use aoc::*;
+use itertools::Itertools;
+use num::{BigInt, BigRational, Signed, Zero};
+use std::ops::RangeInclusive;
+
+type Data = Vec<Hailstone>;
+type SolutionType = i64;
+
+pub const TEST_AREA: RangeInclusive<i64> = 200000000000000..=400000000000000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Hailstone {
+    pub pos: Vec3,
+    pub vel: Vec3,
+}
+
+fn rational(v: impl Into<BigInt>) -> BigRational {
+    BigRational::from_integer(v.into())
+}
+
+/// Where the paths of `a` and `b` cross in the x-y plane, ignoring z and
+/// time, if both hailstones get there in the future. Parallel paths never
+/// count, even if they coincide.
+pub fn crossing_2d(a: &Hailstone, b: &Hailstone) -> Option<[BigRational; 2]> {
+    let [ax, ay, _] = a.pos.map(rational);
+    let [avx, avy, _] = a.vel.map(rational);
+    let [bx, by, _] = b.pos.map(rational);
+    let [bvx, bvy, _] = b.vel.map(rational);
+    let det = &avx * &bvy - &avy * &bvx;
+    if det.is_zero() {
+        return None;
+    }
+    let (dx, dy) = (&bx - &ax, &by - &ay);
+    let t = (&dx * &bvy - &dy * &bvx) / &det;
+    let s = (&dx * &avy - &dy * &avx) / &det;
+    if t.is_negative() || s.is_negative() {
+        return None;
+    }
+    Some([ax + &t * avx, ay + t * avy])
+}
+
+/// Pairs of hailstones whose future paths cross inside `area` in both x and y.
+pub fn crossings_in(hail: &[Hailstone], area: RangeInclusive<i64>) -> usize {
+    let (lo, hi) = (rational(*area.start()), rational(*area.end()));
+    hail.iter()
+        .tuple_combinations()
+        .filter_map(|(a, b)| crossing_2d(a, b))
+        .filter(|p| p.iter().all(|c| &lo <= c && c <= &hi))
+        .count()
+}
+
+/// Gauss-Jordan elimination over the rationals. `None` if the system has no
+/// unique solution.
+fn solve_linear(mut rows: Vec<Vec<BigRational>>) -> Option<Vec<BigRational>> {
+    let n = rows.len();
+    for col in 0..n {
+        let pivot = (col..n).find(|&r| !rows[r][col].is_zero())?;
+        rows.swap(col, pivot);
+        let pivot = rows[col].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            if r != col && !row[col].is_zero() {
+                let factor = &row[col] / &pivot[col];
+                for (v, p) in row.iter_mut().zip(&pivot).skip(col) {
+                    *v -= &factor * p;
+                }
+            }
+        }
+    }
+    Some(
+        rows.iter()
+            .enumerate()
+            .map(|(i, row)| &row[n] / &row[i])
+            .collect(),
+    )
+}
+
+fn cross(a: [i128; 3], b: [i128; 3]) -> [i128; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// The rock that hits every hailstone, from the first ones that pin it down.
+///
+/// Hitting hailstone `i` at some time means `(P - p_i) × (V - v_i) = 0`. The
+/// `P × V` term is shared by all hailstones, so subtracting the equations of
+/// `i` and `j` leaves three linear ones in `P` and `V`:
+/// `P × (v_j - v_i) + (p_j - p_i) × V = p_j × v_j - p_i × v_i`.
+/// Two pairs give the six equations to solve exactly. `None` if no pair of
+/// pairs has a unique, integral solution.
+pub fn throw_rock(hail: &[Hailstone]) -> Option<Hailstone> {
+    let wide = |v: Vec3| v.map(i128::from);
+    let equations = |i: usize, j: usize| {
+        let (a, b) = (&hail[i], &hail[j]);
+        let d = wide(vec_sub(b.vel, a.vel));
+        let dp = wide(vec_sub(b.pos, a.pos));
+        let (pa, pb) = (
+            cross(wide(a.pos), wide(a.vel)),
+            cross(wide(b.pos), wide(b.vel)),
+        );
+        let rhs = [0, 1, 2].map(|k| pb[k] - pa[k]);
+        [
+            [0, d[2], -d[1], 0, -dp[2], dp[1], rhs[0]],
+            [-d[2], 0, d[0], dp[2], 0, -dp[0], rhs[1]],
+            [d[1], -d[0], 0, -dp[1], dp[0], 0, rhs[2]],
+        ]
+    };
+    (1..hail.len()).tuple_combinations().find_map(|(j, k)| {
+        let rows = equations(0, j)
+            .into_iter()
+            .chain(equations(0, k))
+            .map(|row| row.into_iter().map(rational).collect())
+            .collect();
+        let solution = solve_linear(rows)?;
+        let ints = solution
+            .iter()
+            .map(|v| v.is_integer().then(|| i64::try_from(v.to_integer()).ok())?)
+            .collect::<Option<Vec<_>>>()?;
+        Some(Hailstone {
+            pos: [ints[0], ints[1], ints[2]],
+            vel: [ints[3], ints[4], ints[5]],
+        })
+    })
+}
+
+#[aoc_generator(day24)]
+pub fn generator(input: &str) -> Data {
+    let triple = |t: &str| -> Vec3 {
+        let (x, y, z) = t
+            .split(',')
+            .map(|v| v.trim().parse().unwrap())
+            .collect_tuple()
+            .unwrap();
+        [x, y, z]
+    };
+    input
+        .lines()
+        .map(|line| {
+            let (pos, vel) = line.split_once('@').unwrap();
+            Hailstone {
+                pos: triple(pos),
+                vel: triple(vel),
+            }
+        })
+        .collect()
+}
+
+#[aoc(day24, part1)]
+pub fn part1(input: &Data) -> usize {
+    crossings_in(input, TEST_AREA)
+}
+
+#[aoc(day24, part2)]
+pub fn part2(input: &Data) -> SolutionType {
+    throw_rock(input).unwrap().pos.iter().sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "19, 13, 30 @ -2,  1, -2
+18, 19, 22 @ -1, -1, -2
+20, 25, 34 @ -2, -2, -4
+12, 31, 28 @ -1, -2, -1
+20, 19, 15 @  1, -5, -3";
+
+    #[test]
+    fn part1_example() {
+        assert_eq!(crossings_in(&generator(SAMPLE), 7..=27), 2);
+    }
+
+    #[test]
+    fn part2_example() {
+        assert_eq!(part2(&generator(SAMPLE)), 47);
+        assert_eq!(
+            throw_rock(&generator(SAMPLE)),
+            Some(Hailstone {
+                pos: [24, 13, 10],
+                vel: [-3, 1, 2]
+            })
+        );
+    }
+
+    #[test]
+    fn crossings() {
+        let hail = generator(SAMPLE);
+        let ratio = |n: i64, d: i64| BigRational::new(n.into(), d.into());
+        assert_eq!(
+            crossing_2d(&hail[0], &hail[1]),
+            Some([ratio(43, 3), ratio(46, 3)])
+        );
+        // Parallel.
+        assert_eq!(crossing_2d(&hail[1], &hail[2]), None);
+        // In the past for the first hailstone.
+        assert_eq!(crossing_2d(&hail[0], &hail[4]), None);
+    }
+
+    #[test]
+    fn large_coordinates() {
+        // Coordinates on the scale of the real input, with hail the rock hits
+        // at times 3, 5, 11 and 13.
+        let rock = Hailstone {
+            pos: [
+                304_012_345_678_901,
+                211_987_654_321_017,
+                389_000_000_000_003,
+            ],
+            vel: [-17, 42, 5],
+        };
+        let hail = [
+            (3, [101, -7, 13]),
+            (5, [-203, 61, -88]),
+            (11, [37, 29, -41]),
+            (13, [-9, -3, 77]),
+        ]
+        .map(|(t, vel): (i64, Vec3)| Hailstone {
+            pos: vec_sub(vec_add(rock.pos, vec_mul(rock.vel, t)), vec_mul(vel, t)),
+            vel,
+        });
+        assert_eq!(throw_rock(&hail), Some(rock));
+    }
+}
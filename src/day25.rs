@@ -0,0 +1,294 @@
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::{BinaryHeap, HashMap};
+
+type SolutionType = usize;
+
+/// Components interned by name, with each wire listed once in `edges` and in
+/// both directions in `adjacency`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wiring {
+    names: Vec<String>,
+    adjacency: Vec<Vec<usize>>,
+    edges: Vec<(usize, usize)>,
+}
+
+/// A split of the components into two groups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cut {
+    /// Components on either side.
+    pub sizes: [usize; 2],
+    /// Wires between the sides, as `(smaller index, larger index)` in order.
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl Wiring {
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn name(&self, component: usize) -> &str {
+        &self.names[component]
+    }
+
+    /// The wires of `cut` by component name.
+    pub fn edge_names(&self, cut: &Cut) -> Vec<(&str, &str)> {
+        cut.edges
+            .iter()
+            .map(|&(a, b)| (self.name(a), self.name(b)))
+            .collect()
+    }
+
+    fn cut(&self, side: &[bool]) -> Cut {
+        let inside = side.iter().filter(|&&s| s).count();
+        let mut edges = self
+            .edges
+            .iter()
+            .copied()
+            .filter(|&(a, b)| side[a] != side[b])
+            .collect::<Vec<_>>();
+        edges.sort();
+        Cut {
+            sizes: [inside, self.len() - inside],
+            edges,
+        }
+    }
+
+    /// A minimum cut by Stoer–Wagner. Each phase orders the merged vertices
+    /// by how strongly they connect to those before them; the last one, cut
+    /// off from the rest, is the best cut separating the last two, which are
+    /// then merged. The best phase is a global minimum. `None` with fewer
+    /// than two components.
+    pub fn stoer_wagner(&self) -> Option<Cut> {
+        let n = self.len();
+        if n < 2 {
+            return None;
+        }
+        let mut weights = self
+            .adjacency
+            .iter()
+            .map(|adj| adj.iter().map(|&to| (to, 1)).collect::<HashMap<_, _>>())
+            .collect::<Vec<_>>();
+        let mut members = (0..n).map(|v| vec![v]).collect::<Vec<_>>();
+        let mut active = (0..n).collect::<Vec<_>>();
+        let mut best: Option<(usize, Vec<usize>)> = None;
+
+        while active.len() > 1 {
+            let mut connection = vec![0; n];
+            let mut added = vec![false; n];
+            let mut heap = BinaryHeap::new();
+            let (mut prev, mut last, mut cut_weight) = (usize::MAX, usize::MAX, 0);
+            // Restarts at weight 0 whenever a disconnected part is left.
+            while let Some(&start) = active.iter().find(|&&v| !added[v]) {
+                heap.push((0, start));
+                while let Some((w, v)) = heap.pop() {
+                    if added[v] || w != connection[v] {
+                        continue;
+                    }
+                    added[v] = true;
+                    (prev, last, cut_weight) = (last, v, w);
+                    for (&to, &weight) in &weights[v] {
+                        if !added[to] {
+                            connection[to] += weight;
+                            heap.push((connection[to], to));
+                        }
+                    }
+                }
+            }
+            if best.as_ref().is_none_or(|(w, _)| cut_weight < *w) {
+                best = Some((cut_weight, members[last].clone()));
+            }
+
+            // Merge `last` into `prev`.
+            let moved = std::mem::take(&mut members[last]);
+            members[prev].extend(moved);
+            for (to, weight) in std::mem::take(&mut weights[last]) {
+                weights[to].remove(&last);
+                if to != prev {
+                    *weights[prev].entry(to).or_insert(0) += weight;
+                    *weights[to].entry(prev).or_insert(0) += weight;
+                }
+            }
+            active.retain(|&v| v != last);
+        }
+
+        let mut side = vec![false; n];
+        for v in best?.1 {
+            side[v] = true;
+        }
+        Some(self.cut(&side))
+    }
+
+    /// Karger's randomized contraction: merge the ends of randomly ordered
+    /// wires until two groups are left. Runs up to `trials` times and stops
+    /// early at a cut of at most `enough` wires. Returns the smallest cut
+    /// found, `None` with fewer than two components.
+    pub fn karger<R: Rng>(&self, rng: &mut R, trials: usize, enough: usize) -> Option<Cut> {
+        if self.len() < 2 {
+            return None;
+        }
+        let mut best: Option<Cut> = None;
+        let mut edges = self.edges.clone();
+        for _ in 0..trials {
+            edges.shuffle(rng);
+            let mut parent = (0..self.len()).collect::<Vec<_>>();
+            let find = |parent: &mut Vec<usize>, mut v: usize| {
+                while parent[v] != v {
+                    parent[v] = parent[parent[v]];
+                    v = parent[v];
+                }
+                v
+            };
+            let mut groups = self.len();
+            for &(a, b) in &edges {
+                if groups == 2 {
+                    break;
+                }
+                let (a, b) = (find(&mut parent, a), find(&mut parent, b));
+                if a != b {
+                    parent[a] = b;
+                    groups -= 1;
+                }
+            }
+            // A disconnected graph may stop with more groups; split off the
+            // first one.
+            let root = find(&mut parent, 0);
+            let side = (0..self.len())
+                .map(|v| find(&mut parent, v) == root)
+                .collect::<Vec<_>>();
+            let cut = self.cut(&side);
+            if best
+                .as_ref()
+                .is_none_or(|b| cut.edges.len() < b.edges.len())
+            {
+                best = Some(cut);
+            }
+            if best.as_ref().is_some_and(|b| b.edges.len() <= enough) {
+                break;
+            }
+        }
+        best
+    }
+}
+
+#[aoc_generator(day25)]
+pub fn generator(input: &str) -> Wiring {
+    let mut wiring = Wiring {
+        names: vec![],
+        adjacency: vec![],
+        edges: vec![],
+    };
+    let mut index = HashMap::new();
+    let mut intern = |name: &str, wiring: &mut Wiring| {
+        *index.entry(name.to_string()).or_insert_with(|| {
+            wiring.names.push(name.to_string());
+            wiring.adjacency.push(vec![]);
+            wiring.names.len() - 1
+        })
+    };
+    for line in input.lines().filter(|l| !l.is_empty()) {
+        let (from, to) = line.split_once(": ").unwrap();
+        let from = intern(from, &mut wiring);
+        for to in to.split_whitespace() {
+            let to = intern(to, &mut wiring);
+            if from != to && !wiring.adjacency[from].contains(&to) {
+                wiring.adjacency[from].push(to);
+                wiring.adjacency[to].push(from);
+                wiring.edges.push((from.min(to), from.max(to)));
+            }
+        }
+    }
+    wiring
+}
+
+#[aoc(day25, part1)]
+pub fn part1(input: &Wiring) -> SolutionType {
+    let cut = input.stoer_wagner().unwrap();
+    cut.sizes[0] * cut.sizes[1]
+}
+
+#[aoc(day25, part1, Karger)]
+pub fn part1_karger(input: &Wiring) -> SolutionType {
+    let mut rng = SmallRng::seed_from_u64(25);
+    let cut = input.karger(&mut rng, usize::MAX, 3).unwrap();
+    cut.sizes[0] * cut.sizes[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "jqt: rhn xhk nvd
+rsh: frs pzl lsr
+xhk: hfx
+cmg: qnr nvd lhk bvb
+rhn: xhk bvb hfx
+bvb: xhk hfx
+pzl: lsr hfx nvd
+qnr: nvd
+ntq: jqt hfx bvb xhk
+nvd: lhk
+lsr: lhk
+rzs: qnr cmg lsr rsh
+frs: qnr lhk lsr";
+
+    fn sorted_names(wiring: &Wiring, cut: &Cut) -> Vec<(String, String)> {
+        let mut names = wiring
+            .edge_names(cut)
+            .into_iter()
+            .map(|(a, b)| (a.min(b).to_string(), a.max(b).to_string()))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn part1_example() {
+        let wiring = generator(SAMPLE);
+        assert_eq!(part1(&wiring), 54);
+        assert_eq!(part1_karger(&wiring), 54);
+    }
+
+    #[test]
+    fn cut_edges() {
+        let wiring = generator(SAMPLE);
+        assert_eq!(wiring.len(), 15);
+        let expected = [("bvb", "cmg"), ("hfx", "pzl"), ("jqt", "nvd")]
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .to_vec();
+
+        let cut = wiring.stoer_wagner().unwrap();
+        assert_eq!(sorted_names(&wiring, &cut), expected);
+        let mut sizes = cut.sizes;
+        sizes.sort();
+        assert_eq!(sizes, [6, 9]);
+
+        let mut rng = SmallRng::seed_from_u64(1);
+        let cut = wiring.karger(&mut rng, 1000, 3).unwrap();
+        assert_eq!(sorted_names(&wiring, &cut), expected);
+    }
+
+    #[test]
+    fn small_graphs() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let single = generator("");
+        assert_eq!(single.stoer_wagner(), None);
+        assert_eq!(single.karger(&mut rng, 10, 0), None);
+
+        let apart = generator("a: b\nc: d");
+        let cut = apart.stoer_wagner().unwrap();
+        assert_eq!(cut.edges, []);
+        assert_eq!(cut.sizes[0] + cut.sizes[1], 4);
+        assert_eq!(apart.karger(&mut rng, 10, 0).unwrap().edges, []);
+
+        // A triangle with a tail hangs on one wire.
+        let tail = generator("a: b c\nb: c\nc: d");
+        let cut = tail.stoer_wagner().unwrap();
+        assert_eq!(tail.edge_names(&cut), [("c", "d")]);
+    }
+}
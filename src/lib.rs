@@ -18,18 +18,20 @@ pub mod day09;
 pub mod day10;
 pub mod day11;
 pub mod day12;
-pub mod help;
 pub mod day13;
 pub mod day14;
 pub mod day15;
 pub mod day16;
 pub mod day17;
-//pub mod day19;
-//pub mod day20;
-//pub mod day21;
-//pub mod day22;
-//pub mod day23;
-//pub mod day24;
-//pub mod day25;
+pub mod day18;
+pub mod day19;
+pub mod day20;
+pub mod day21;
+pub mod day22;
+pub mod day23;
+pub mod day24;
+pub mod day25;
+pub mod help;
+pub mod nonogram;
 
 aoc_lib! { year = 2023 }
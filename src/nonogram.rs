@@ -0,0 +1,213 @@
+//! 2D nonogram solver built on the day12 row counter.
+//!
+//! Puzzles are written as the row clues, a blank line, then the column clues.
+//! Each clue is one line of comma separated block sizes, `0` for an empty line:
+//!
+//! ```text
+//! 1,1
+//! 3
+//!
+//! 1
+//! 2
+//! 1,1
+//! ```
+
+use crate::day12::Table;
+use std::fmt::Formatter;
+
+const UNKNOWN: u8 = b'?';
+const FILLED: u8 = b'#';
+const EMPTY: u8 = b'.';
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Puzzle {
+    rows: Vec<Vec<usize>>,
+    cols: Vec<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Grid {
+    cells: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Unsolvable,
+    Unique(Grid),
+    Multiple(Grid, Grid),
+}
+
+impl std::fmt::Display for Grid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for row in &self.cells {
+            writeln!(f, "{}", String::from_utf8_lossy(row))?;
+        }
+        Ok(())
+    }
+}
+
+impl Grid {
+    fn new(width: usize, height: usize) -> Grid {
+        Grid {
+            cells: vec![vec![UNKNOWN; width]; height],
+        }
+    }
+
+    fn column(&self, x: usize) -> Vec<u8> {
+        self.cells.iter().map(|row| row[x]).collect()
+    }
+
+    fn first_unknown(&self) -> Option<(usize, usize)> {
+        self.cells
+            .iter()
+            .enumerate()
+            .find_map(|(y, row)| row.iter().position(|&c| c == UNKNOWN).map(|x| (x, y)))
+    }
+
+    pub fn is_filled(&self, x: usize, y: usize) -> bool {
+        self.cells[y][x] == FILLED
+    }
+}
+
+/// Returns the cells every arrangement of `clue` agrees on, or `None` if the
+/// line has no valid arrangement.
+pub fn line_solve(line: &[u8], clue: &[usize]) -> Option<Vec<u8>> {
+    let table = Table::new(line, clue);
+    if table.count() == 0 {
+        return None;
+    }
+    let solved = line
+        .iter()
+        .zip(table.spring_counts())
+        .map(|(&cell, counts)| match counts {
+            [_, 0] => EMPTY,
+            [0, _] => FILLED,
+            _ => cell,
+        })
+        .collect();
+    Some(solved)
+}
+
+impl Puzzle {
+    pub fn parse(input: &str) -> Puzzle {
+        let parse_clues = |block: &str| {
+            block
+                .lines()
+                .map(|line| {
+                    line.split(',')
+                        .map(|n| n.trim().parse().unwrap())
+                        .filter(|&n| n != 0)
+                        .collect()
+                })
+                .collect()
+        };
+        let (rows, cols) = input.trim().split_once("\n\n").unwrap();
+        Puzzle {
+            rows: parse_clues(rows),
+            cols: parse_clues(cols),
+        }
+    }
+
+    /// Line-solves rows and columns until nothing changes. Returns `false` on a
+    /// contradiction.
+    fn propagate(&self, grid: &mut Grid) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (y, clue) in self.rows.iter().enumerate() {
+                match line_solve(&grid.cells[y], clue) {
+                    Some(line) => {
+                        changed |= line != grid.cells[y];
+                        grid.cells[y] = line;
+                    }
+                    None => return false,
+                }
+            }
+            for (x, clue) in self.cols.iter().enumerate() {
+                let column = grid.column(x);
+                match line_solve(&column, clue) {
+                    Some(line) => {
+                        changed |= line != column;
+                        for (y, c) in line.into_iter().enumerate() {
+                            grid.cells[y][x] = c;
+                        }
+                    }
+                    None => return false,
+                }
+            }
+        }
+        true
+    }
+
+    fn search(&self, mut grid: Grid, found: &mut Vec<Grid>) {
+        if found.len() >= 2 || !self.propagate(&mut grid) {
+            return;
+        }
+        match grid.first_unknown() {
+            None => found.push(grid),
+            Some((x, y)) => {
+                for c in [FILLED, EMPTY] {
+                    let mut next = grid.clone();
+                    next.cells[y][x] = c;
+                    self.search(next, found);
+                }
+            }
+        }
+    }
+
+    /// Solves by propagation, backtracking on the first undecided cell, and stops
+    /// once a second solution proves the puzzle ambiguous.
+    pub fn solve(&self) -> Outcome {
+        let mut found = Vec::new();
+        self.search(Grid::new(self.cols.len(), self.rows.len()), &mut found);
+        let mut found = found.into_iter();
+        match (found.next(), found.next()) {
+            (None, _) => Outcome::Unsolvable,
+            (Some(a), None) => Outcome::Unique(a),
+            (Some(a), Some(b)) => Outcome::Multiple(a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "1
+3
+1,1
+3
+1
+
+3
+2,2
+3
+0
+0";
+
+    #[test]
+    fn test_line_solve() {
+        assert_eq!(line_solve(b"?????", &[4]).unwrap(), b"?###?");
+        assert_eq!(line_solve(b"?????", &[]).unwrap(), b".....");
+        assert_eq!(line_solve(b"#????", &[2, 1]).unwrap(), b"##.??");
+        assert_eq!(line_solve(b"??.??", &[3]), None);
+    }
+
+    #[test]
+    fn test_unique() {
+        let Outcome::Unique(grid) = Puzzle::parse(SAMPLE).solve() else {
+            panic!("expected a unique solution");
+        };
+        assert_eq!(grid.to_string(), ".#...\n###..\n#.#..\n###..\n.#...\n");
+    }
+
+    #[test]
+    fn test_multiple_and_unsolvable() {
+        let Outcome::Multiple(a, b) = Puzzle::parse("1\n1\n\n1\n1").solve() else {
+            panic!("expected two solutions");
+        };
+        assert_ne!(a, b);
+        assert!(a.is_filled(0, 0) ^ b.is_filled(0, 0));
+        assert_eq!(Puzzle::parse("2\n0\n\n0\n0").solve(), Outcome::Unsolvable);
+    }
+}